@@ -1,5 +1,7 @@
 
 use url::Url;
+use url::form_urlencoded::Serializer;
+use json::push_json_string;
 
 #[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
 pub enum AuthzErrorCode {
@@ -16,7 +18,19 @@ pub enum AuthzErrorCode {
     #[serde(rename="server_error")]
     ServerError,
     #[serde(rename="temporarily_unavailable")]
-    TemporarilyUnavailable
+    TemporarilyUnavailable,
+    /// OpenID Connect Core section 3.1.2.6
+    #[serde(rename="interaction_required")]
+    InteractionRequired,
+    #[serde(rename="login_required")]
+    LoginRequired,
+    #[serde(rename="account_selection_required")]
+    AccountSelectionRequired,
+    #[serde(rename="consent_required")]
+    ConsentRequired,
+    /// RFC 9101 section 6.2
+    #[serde(rename="invalid_request_object")]
+    InvalidRequestObject,
 }
 
 impl From<AuthzErrorCode> for &'static str {
@@ -29,6 +43,11 @@ impl From<AuthzErrorCode> for &'static str {
             AuthzErrorCode::InvalidScope => "invalid_scope",
             AuthzErrorCode::ServerError => "server_error",
             AuthzErrorCode::TemporarilyUnavailable => "temporarily_unavailable",
+            AuthzErrorCode::InteractionRequired => "interaction_required",
+            AuthzErrorCode::LoginRequired => "login_required",
+            AuthzErrorCode::AccountSelectionRequired => "account_selection_required",
+            AuthzErrorCode::ConsentRequired => "consent_required",
+            AuthzErrorCode::InvalidRequestObject => "invalid_request_object",
         }
     }
 }
@@ -42,6 +61,21 @@ pub struct AuthzError {
 }
 
 impl AuthzError {
+    /// AuthzErrors are usually returned via the redirect_uri, but are returned
+    /// directly as JSON from the pushed authorization request endpoint (RFC 9126
+    /// section 2.3).  This function skips fields that are None.
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"error\": {}",
+                                   ::serde_json::to_string(
+                                       <&'static str as From<AuthzErrorCode>>::from(self.error))
+                                   .unwrap());
+        push_json_string(&mut json_str, "error_description", &self.error_description);
+        push_json_string(&mut json_str, "error_uri", &self.error_uri);
+        push_json_string(&mut json_str, "state", &self.state);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+
     // AuthzErrors are returned via the return_url query string parameters.
     // This function adds the error fields to the given url
     pub fn put_into_query_string(&self, url: &mut Url) {
@@ -60,4 +94,21 @@ impl AuthzError {
                 .append_pair("state", self.state.as_ref().unwrap());
         }
     }
+
+    // AuthzErrors for the implicit grant are returned via the return_url fragment.
+    // This function sets the fragment of the given url to the error fields
+    pub fn put_into_fragment(&self, url: &mut Url) {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("error", <&'static str as From<AuthzErrorCode>>::from(self.error));
+        if self.error_description.is_some() {
+            serializer.append_pair("error_description", self.error_description.as_ref().unwrap());
+        }
+        if self.error_uri.is_some() {
+            serializer.append_pair("error_uri", self.error_uri.as_ref().unwrap());
+        }
+        if self.state.is_some() {
+            serializer.append_pair("state", self.state.as_ref().unwrap());
+        }
+        url.set_fragment(Some(&*serializer.finish()));
+    }
 }
@@ -1,12 +1,14 @@
 
-use {ClientId, RedirectUri};
+use {ClientId, RedirectUri, ResponseType, CodeChallenge};
 
 /// This is the data that the client sends to the authz_server when requesting an
-/// authorization grant, as defined in RFC 6749 section 4.1.1
+/// authorization grant, as defined in RFC 6749 sections 4.1.1 and 4.2.1, and OpenID
+/// Connect Core section 3.1.2.1
 #[derive(Clone, Debug)]
 pub struct AuthzRequest {
-    // We do not support response_type other than "code" at present
-    // pub response_type: String,
+    /// response_type as supplied in the request.  If the request was missing or had an
+    /// unsupported response_type (and thus has an error), this will be `Code`.
+    pub response_type: ResponseType,
 
     /// client_id as supplied in the request
     pub client_id: ClientId,
@@ -17,7 +19,30 @@ pub struct AuthzRequest {
     /// scope as supplied in the request
     pub scope: Option<String>,
 
+    /// PKCE code challenge (RFC 7636) as supplied in the request, or None if not
+    /// supplied.  You must store this with the authorization code you issue.
+    pub code_challenge: Option<CodeChallenge>,
+
     /// state as supplied in the request.  We recommend implementations should
     /// error if a state was not supplied in the request.
     pub state: Option<String>,
+
+    /// OpenID Connect nonce as supplied in the request, to be echoed in the ID token.
+    /// You must store this with the authorization code you issue.
+    pub nonce: Option<String>,
+
+    /// OpenID Connect prompt values as supplied in the request (OpenID Connect Core
+    /// section 3.1.2.1).  If this contains `none`, you must not display any
+    /// authentication or consent user interface, and should deny the request with
+    /// `login_required`, `consent_required` or `interaction_required` as applicable.
+    pub prompt: Vec<String>,
+
+    /// OpenID Connect max_age as supplied in the request: the allowable elapsed time
+    /// in seconds since the end-user last actively authenticated.  If exceeded, you
+    /// must re-authenticate the end-user.
+    pub max_age: Option<u64>,
+
+    /// OpenID Connect login_hint as supplied in the request, a hint about the
+    /// end-user's login identifier
+    pub login_hint: Option<String>,
 }
@@ -1,34 +1,76 @@
 
 use std::io::{Write,Read};
+use std::collections::{HashMap, BTreeMap};
 use hyper::server::{Request, Response};
 use hyper::uri::RequestUri;
-use hyper::header::{Authorization, Basic, Location, ContentType, CacheDirective, CacheControl,
-                    Pragma};
+use hyper::method::Method;
+use hyper::header::{Authorization, Basic, Bearer, Location, ContentType, CacheDirective,
+                    CacheControl, Pragma};
 use hyper::status::StatusCode;
 use url::Url;
+use url::form_urlencoded::Serializer;
+use serde_json::Value;
+use textnonce::TextNonce;
+use openssl::crypto::memcmp;
 use {ClientData, OAuthError, AuthzError, AuthzErrorCode, TokenError, TokenErrorCode,
-     AuthzRequest, TokenData, ClientId, RedirectUri};
+     AuthzRequest, ClientAuthorization, TokenData, ClientId, ClientType, RedirectUri,
+     ResponseType, CodeChallenge, CodeChallengeMethod, TokenTypeHint, TokenIntrospection,
+     AuthzServerMetadata, ProviderMetadata, ClientMetadata, ClientRegistration, RegistrationError,
+     RegistrationErrorCode, BearerError, BearerErrorCode, IdTokenClaims, Jwt, JwsKey,
+     KeySet, DeviceAuthorization, DevicePollResult, TokenType, TokenExchangeMode,
+     TokenExchangeRequest, DeliveryMode, BackchannelAuthRequest, BackchannelAuthResponse,
+     BackchannelAuthResult, ClientAuthnMethod, HmacKey};
+use syntax::{valid_code_str, valid_refresh_token_str, valid_scope_str, valid_username_str,
+             valid_password_str, valid_code_challenge_str, valid_code_verifier_str};
+use scope::{scope_is_subset, scope_contains};
+use resource_server::extract_bearer_token;
+use userinfo::filter_claims;
+use jwk::jwk_set_json;
+use request_object::verify_request_object;
+use token_exchange::TOKEN_EXCHANGE_GRANT_TYPE;
+use jwt_bearer::{verify_jwt_assertion, JWT_BEARER_GRANT_TYPE};
+use client_authn_method::{verify_client_assertion, CLIENT_ASSERTION_TYPE};
+use saml2_bearer::{decode_saml2_assertion, SAML2_BEARER_GRANT_TYPE};
+use backchannel_authentication::{send_backchannel_notification, CIBA_GRANT_TYPE,
+                                 BACKCHANNEL_AUTH_LIFETIME, BACKCHANNEL_POLL_INTERVAL};
+use device_authorization::{generate_user_code, DEVICE_CODE_GRANT_TYPE, DEVICE_CODE_LIFETIME,
+                           DEVICE_POLL_INTERVAL};
 
 
 header! { (WwwAuthenticate, "WWW-Authenticate") => [String] }
 
-macro_rules! token_response_fail {
-    ($response:ident, $status_opt:expr, $error:expr, $description:expr, $uri:expr) => {
+/// Prefix of the request URIs issued for pushed authorization requests
+/// (RFC 9126 section 2.2)
+const PUSHED_REQUEST_URI_PREFIX: &'static str = "urn:ietf:params:oauth:request_uri:";
+
+/// Lifetime of pushed authorization requests, in seconds
+const PUSHED_REQUEST_LIFETIME: u64 = 60;
+
+macro_rules! token_response_error {
+    ($response:ident, $status_opt:expr, $error_data:expr) => {
         {
             *$response.status_mut() = match $status_opt {
                 Some(s) => s,
                 None => StatusCode::BadRequest
             };
+            let body = $error_data.as_json();
+            let mut response = $response.start().unwrap();
+            response.write_all(body.as_bytes()).unwrap();
+            let _ = response.end();
+            return;
+        }
+    };
+}
+
+macro_rules! token_response_fail {
+    ($response:ident, $status_opt:expr, $error:expr, $description:expr, $uri:expr) => {
+        {
             let error_data = TokenError {
                 error: $error,
                 error_description: $description.map(|s: &str| s.to_owned()),
                 error_uri: $uri,
             };
-            let body = error_data.as_json();
-            let mut response = $response.start().unwrap();
-            response.write_all(body.as_bytes()).unwrap();
-            let _ = response.end();
-            return;
+            token_response_error!($response, $status_opt, error_data)
         }
     };
     ($response:ident, $status_opt:expr, $error:expr, $description:expr) => {
@@ -39,6 +81,40 @@ macro_rules! token_response_fail {
     };
 }
 
+// rfc6749 section 5.2: if the client attempted to authenticate via the Authorization
+// header, an invalid_client error MUST use 401 and include WWW-Authenticate
+macro_rules! client_authentication_fail {
+    ($request:ident, $response:ident, $error_data:expr) => {
+        {
+            let error_data = $error_data;
+            if error_data.error == TokenErrorCode::InvalidClient &&
+                $request.headers.has::<Authorization<Basic>>()
+            {
+                $response.headers_mut().set(WwwAuthenticate("Basic".to_owned()));
+                token_response_error!($response, Some(StatusCode::Unauthorized), error_data)
+            }
+            token_response_error!($response, None, error_data)
+        }
+    };
+}
+
+macro_rules! registration_response_fail {
+    ($response:ident, $error:expr, $description:expr) => {
+        {
+            *$response.status_mut() = StatusCode::BadRequest;
+            let error_data = RegistrationError {
+                error: $error,
+                error_description: $description.map(|s: &str| s.to_owned()),
+            };
+            let body = error_data.as_json();
+            let mut response = $response.start().unwrap();
+            response.write_all(body.as_bytes()).unwrap();
+            let _ = response.end();
+            return;
+        }
+    };
+}
+
 pub trait AuthzServer<C>
 {
     /// Fetch data about a registered OAuth 2.0 client (clients are the other websites
@@ -53,14 +129,571 @@ pub trait AuthzServer<C>
     fn fetch_client_data(&self, context: &mut C, client_id: &ClientId)
                          -> Result<Option<ClientData>, OAuthError>;
 
-    /// Retrieve the data associated with an issued authentication code.
+    /// Retrieve the data associated with an issued authentication code, as stored when
+    /// the code was issued (see `ClientAuthorization::from_request()`).
     fn retrieve_client_authorization(&self, context: &mut C, code: &str)
-                                     -> Result<(ClientId, RedirectUri), OAuthError>;
+                                     -> Result<ClientAuthorization, OAuthError>;
 
     /// Issue token to client, recording the issuance internally.
     fn issue_token_to_client(&mut self, context: &mut C, code: &str, client_id: &ClientId)
                              -> Result<TokenData, OAuthError>;
 
+    /// Retrieve the data associated with an issued refresh token: the client it was
+    /// issued to, and the scope that was originally granted (None if no scope was
+    /// recorded).  Return an error if the refresh token is unknown, expired or revoked.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which causes the `refresh_token` grant to be rejected as unsupported.  Override
+    /// this (and `rotate_refresh_token()`) to support refresh tokens.
+    fn retrieve_refresh_token(&self, _context: &mut C, _refresh_token: &str)
+                              -> Result<(ClientId, Option<String>), OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue a new access token to the client in exchange for a refresh token, recording
+    /// the issuance internally.  `scope` is the scope of the new access token, which has
+    /// already been verified to not exceed the originally granted scope.
+    ///
+    /// You may rotate the refresh token by including a new one in the returned
+    /// `TokenData`, in which case you should revoke the old one (rfc6749 section 6).
+    fn rotate_refresh_token(&mut self, _context: &mut C, _refresh_token: &str,
+                            _client_id: &ClientId, _scope: Option<&str>)
+                            -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue token to a confidential client on its own behalf (the client credentials
+    /// grant, rfc6749 section 4.4), recording the issuance internally.  `scope` has
+    /// already been verified against `ClientData.scope`.  No refresh token should be
+    /// included (rfc6749 section 4.4.3).
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which causes the `client_credentials` grant to be rejected as unsupported.
+    fn issue_token_for_client_credentials(&mut self, _context: &mut C,
+                                          _client_id: &ClientId, _scope: Option<&str>)
+                                          -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Whether the given client may use the resource owner password credentials grant
+    /// (rfc6749 section 4.3).  This grant exposes the resource owner's password to the
+    /// client, and should only be allowed for highly trusted clients, such as first-party
+    /// applications, when other grant types are not available.
+    ///
+    /// The default implementation returns false, disallowing it for every client.
+    fn allow_password_grant(&self, _context: &mut C, _client_data: &ClientData) -> bool {
+        false
+    }
+
+    /// Verify the resource owner's username and password, as supplied by the client in
+    /// a resource owner password credentials grant.  Return Ok(true) if they are valid,
+    /// Ok(false) if they are not.  You should protect against brute force attacks
+    /// (rfc6749 section 4.3.2).
+    ///
+    /// This is only called if `allow_password_grant()` returns true.
+    fn verify_resource_owner_credentials(&mut self, _context: &mut C, _username: &str,
+                                         _password: &str) -> Result<bool, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue token to client on behalf of the resource owner `username`, whose
+    /// credentials have already been verified, recording the issuance internally.
+    /// `scope` has already been verified against `ClientData.scope`, and defaults to it
+    /// if the client did not request a scope.
+    ///
+    /// This is only called if `allow_password_grant()` returns true.
+    fn issue_token_for_resource_owner(&mut self, _context: &mut C, _client_id: &ClientId,
+                                      _username: &str, _scope: Option<&str>)
+                                      -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Whether the given client may use the implicit grant (rfc6749 section 4.2), where
+    /// the access token is delivered directly to the user-agent in the redirect URI
+    /// fragment.  This is intended only for public clients implemented in a browser
+    /// which cannot use the authorization code grant.
+    ///
+    /// The default implementation returns false, disallowing it for every client.
+    fn allow_implicit_grant(&self, _context: &mut C, _client_data: &ClientData) -> bool {
+        false
+    }
+
+    /// Whether the given client must use PKCE (RFC 7636) with the authorization code
+    /// grant.  If so, authorization requests without a `code_challenge` are rejected.
+    ///
+    /// The default implementation requires PKCE of public clients only.
+    fn require_pkce(&self, _context: &mut C, client_data: &ClientData) -> bool {
+        client_data.client_type == ClientType::PublicClient
+    }
+
+    /// Revoke an access token or refresh token at the request of the client it was
+    /// issued to (RFC 7009).  `token_type_hint` is the client's hint as to which type of
+    /// token it is, if supplied.  If the token is a refresh token, you should also revoke
+    /// access tokens issued from the same authorization grant, and if it is an access
+    /// token you may revoke the associated refresh token too.
+    ///
+    /// Return Ok(()) if the token was revoked, and also if it was not found or was not
+    /// issued to `client_id`, as the response to the client does not distinguish these.
+    /// Return `OAuthError::AuthzTokenTypeUnsupported` if you do not support revoking this
+    /// type of token (which is what the default implementation does).
+    fn revoke_token(&mut self, _context: &mut C, _client_id: &ClientId, _token: &str,
+                    _token_type_hint: Option<TokenTypeHint>) -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzTokenTypeUnsupported)
+    }
+
+    /// Look up the metadata of an access token or refresh token on behalf of a protected
+    /// resource (RFC 7662).  `client_id` identifies the protected resource making the
+    /// request, which has authenticated as a confidential client (public clients are
+    /// refused before this is called), and `token_type_hint` is its hint as to which type
+    /// of token it is, if supplied.
+    ///
+    /// Return `TokenIntrospection::inactive()` if the token is unknown, expired or
+    /// revoked, or if the caller is not permitted to introspect it.  The default
+    /// implementation does so for every token.
+    fn introspect_token(&self, _context: &mut C, _client_id: &ClientId, _token: &str,
+                        _token_type_hint: Option<TokenTypeHint>)
+                        -> Result<TokenIntrospection, OAuthError>
+    {
+        Ok(TokenIntrospection::inactive())
+    }
+
+    /// Get the metadata describing this Authorization Server (RFC 8414), to be served by
+    /// `handle_metadata_request()`.  `AuthzServerMetadata::new()` is a good starting
+    /// point.
+    ///
+    /// The default implementation returns None, publishing no metadata.
+    fn get_metadata(&self, _context: &mut C) -> Option<AuthzServerMetadata> {
+        None
+    }
+
+    /// The URI at which users enter the user codes displayed by their devices
+    /// (RFC 8628 section 3.2).  There you should authenticate the user, look up the
+    /// device authorization by the user code they enter, and ask them whether to
+    /// approve it.
+    ///
+    /// The default implementation returns None, in which case the device
+    /// authorization endpoint is not supported.
+    fn get_device_verification_uri(&self, _context: &mut C) -> Option<String> {
+        None
+    }
+
+    /// Store a new device authorization request (RFC 8628 section 3.1), made by the
+    /// client `client_id` for `scope`.  `scope` has already been verified against
+    /// `ClientData.scope`, and defaults to it if the client did not request a scope.
+    /// You must be able to look it up both by `device_code` (when the device polls) and
+    /// by `user_code` (when the user visits the verification URI).  It expires after
+    /// `expires_in` seconds.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`.
+    fn store_device_authorization(&mut self, _context: &mut C, _client_id: &ClientId,
+                                  _scope: Option<&str>, _device_code: &str,
+                                  _user_code: &str, _expires_in: u64)
+                                  -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Report the state of the device authorization `device_code`, which the client
+    /// `client_id` is polling for (RFC 8628 section 3.4).  If the user has approved
+    /// it, issue an access token and return it as `DevicePollResult::Granted`; the
+    /// device code must then not be accepted again.  Return `SlowDown` if the device
+    /// polls more often than the interval.  Return an error if the device code is
+    /// unknown or was issued to another client.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which results in an `unsupported_grant_type` error.
+    fn poll_device_authorization(&mut self, _context: &mut C, _client_id: &ClientId,
+                                 _device_code: &str)
+                                 -> Result<DevicePollResult, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Start a backchannel authentication request (CIBA Core section 7.1) made by the
+    /// client: notify the user identified by `request.login_hint` (or `login_hint_token`
+    /// or `id_token_hint`) on their own device, showing them the `binding_message` if
+    /// any, and ask them to authenticate and approve the request.  Store the request
+    /// under `auth_req_id`, along with its `client_notification_token` for ping and push
+    /// clients.  It expires after `expires_in` seconds.  `request.scope` has already been
+    /// verified against `ClientData.scope`.
+    ///
+    /// Return `OAuthError::AuthzUnknownUser` if the hint identifies no user, and
+    /// `OAuthError::AuthzBindingMessageInvalid` if the binding message cannot be shown.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`.
+    fn notify_backchannel_user(&mut self, _context: &mut C, _client_data: &ClientData,
+                               _request: &BackchannelAuthRequest, _auth_req_id: &str,
+                               _expires_in: u64) -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Record the outcome of the backchannel authentication request `auth_req_id` of
+    /// the client `client_id`, once the user has approved (in which case tokens have
+    /// been issued) or denied it, or it has expired.  This is called by
+    /// `complete_backchannel_authentication()`.  The outcome must then be reported by
+    /// `poll_backchannel_authentication()`.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`.
+    fn record_backchannel_decision(&mut self, _context: &mut C, _client_id: &ClientId,
+                                   _auth_req_id: &str, _result: &BackchannelAuthResult)
+                                   -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Report the state of the backchannel authentication request `auth_req_id`, which
+    /// the client `client_id` is polling for (CIBA Core section 10.1).  Once a granted
+    /// result has been reported, the `auth_req_id` must not be accepted again.  Return
+    /// `SlowDown` if the client polls more often than the interval.  Return an error if
+    /// the request is unknown or was made by another client.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which results in an `unsupported_grant_type` error.
+    fn poll_backchannel_authentication(&mut self, _context: &mut C, _client_id: &ClientId,
+                                       _auth_req_id: &str)
+                                       -> Result<BackchannelAuthResult, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// The keys trusted to sign JWT assertions issued by `issuer` (RFC 7523 section 3),
+    /// such as a service account's public keys, or an identity provider's.  Return
+    /// Ok(None) if the issuer is not trusted.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which causes the JWT bearer grant to be rejected as unsupported.
+    fn get_assertion_key_set(&self, _context: &mut C, _issuer: &str)
+                             -> Result<Option<Box<KeySet>>, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue token to the client `client_id` on behalf of the subject of a JWT assertion
+    /// (RFC 7523 section 2.1), recording the issuance internally.  The assertion's
+    /// signature, `iss`, `sub`, `aud` and `exp` claims have already been verified.
+    /// `scope` has already been verified against `ClientData.scope`, and defaults to it
+    /// if the client did not request a scope.  You should check that the client may act
+    /// for the subject, and may reject assertions whose `jti` has been seen before.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`.
+    fn issue_token_for_jwt_assertion(&mut self, _context: &mut C, _client_id: &ClientId,
+                                     _assertion: &Jwt, _scope: Option<&str>)
+                                     -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Validate a SAML 2.0 assertion presented by the client `client_id` (RFC 7522
+    /// section 3), and return the subject it identifies.  `assertion` is the XML of the
+    /// assertion, already base64url decoded.  You must check that it is signed by a
+    /// trusted identity provider, that its `<Audience>` is this Authorization Server,
+    /// that its `<SubjectConfirmationData>` has a `Recipient` of the token endpoint and
+    /// an unexpired `NotOnOrAfter`, and that it has not been used before.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which causes the SAML 2.0 bearer grant to be rejected as unsupported.
+    fn validate_saml2_assertion(&mut self, _context: &mut C, _client_id: &ClientId,
+                                _assertion: &[u8]) -> Result<String, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue token to the client `client_id` on behalf of `subject`, as identified by a
+    /// SAML 2.0 assertion which has already been validated (RFC 7522 section 2.1),
+    /// recording the issuance internally.  `scope` has already been verified against
+    /// `ClientData.scope`, and defaults to it if the client did not request a scope.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`.
+    fn issue_token_for_saml2_assertion(&mut self, _context: &mut C, _client_id: &ClientId,
+                                       _subject: &str, _scope: Option<&str>)
+                                       -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Decide whether the client `client_id` may exchange tokens as requested (RFC 8693
+    /// section 2.1), and whether the issued token lets it impersonate the subject or act
+    /// as a delegate on the subject's behalf.  Validate the subject token (and the actor
+    /// token, if any) here, and check the audience or resource is one the client may
+    /// obtain tokens for.
+    ///
+    /// Return `OAuthError::AuthzTargetInvalid` if an audience or resource is not
+    /// acceptable, and `OAuthError::AuthzGrantTypeUnsupported` if you do not support
+    /// token exchange.  Any other error rejects the subject or actor token.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`, as
+    /// tokens must not be exchanged without being validated.
+    fn decide_token_exchange(&mut self, _context: &mut C, _client_id: &ClientId,
+                             _request: &TokenExchangeRequest)
+                             -> Result<TokenExchangeMode, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Issue a token to the client `client_id` in exchange for the subject token (RFC
+    /// 8693 section 2.2), recording the issuance internally.  `request.scope` has already
+    /// been verified against `ClientData.scope`, but should also be limited to what the
+    /// subject token allows.  For delegation, identify the actor in an `act` claim if
+    /// the token is a JWT.
+    ///
+    /// If the token is not an access token, set `TokenData.token_type` to "N_A".  If
+    /// `TokenData.issued_token_type` is not set, it is taken to be the requested type,
+    /// or else an access token.
+    ///
+    /// The default implementation returns `OAuthError::AuthzGrantTypeUnsupported`,
+    /// which causes the token exchange grant to be rejected as unsupported.
+    fn issue_token_for_exchange(&mut self, _context: &mut C, _client_id: &ClientId,
+                                _request: &TokenExchangeRequest, _mode: TokenExchangeMode)
+                                -> Result<TokenData, OAuthError>
+    {
+        Err(OAuthError::AuthzGrantTypeUnsupported)
+    }
+
+    /// Store the parameters of a pushed authorization request (RFC 9126 section 2),
+    /// which have been authenticated and validated, under `request_uri`.  They expire
+    /// after `expires_in` seconds.  The client authentication parameters have been
+    /// removed.
+    ///
+    /// The default implementation returns `OAuthError::AuthzPushedRequestUnsupported`,
+    /// in which case the pushed authorization request endpoint is not supported.
+    fn store_pushed_authz_request(&mut self, _context: &mut C, _request_uri: &str,
+                                  _params: &HashMap<String, String>, _expires_in: u64)
+                                  -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzPushedRequestUnsupported)
+    }
+
+    /// Retrieve the parameters of a pushed authorization request by its `request_uri`
+    /// (RFC 9126 section 4).  Return Ok(None) if it is unknown or has expired.  You
+    /// should also return Ok(None) if it has been retrieved before, as request URIs
+    /// are intended to be used only once.  This is called from `handle_authz_request()`,
+    /// which takes `&self`, so you need interior mutability (such as a `RefCell` or
+    /// `Mutex`) or an external store to delete the request as it is retrieved.
+    ///
+    /// The default implementation returns Ok(None).
+    fn retrieve_pushed_authz_request(&self, _context: &mut C, _request_uri: &str)
+                                     -> Result<Option<HashMap<String, String>>, OAuthError>
+    {
+        Ok(None)
+    }
+
+    /// The keys a client signs with, for verifying its request objects (RFC 9101), and
+    /// the client assertions of clients using `ClientAuthnMethod::PrivateKeyJwt`.
+    /// Typically these are the client's registered public keys, which you can create
+    /// from its JSON Web Key Set with `RsaKey::from_jwk()`.  For HS256, use an
+    /// `HmacKey` made from the client secret.
+    ///
+    /// The default implementation returns Ok(None), in which case request objects and
+    /// `private_key_jwt` client assertions are rejected.
+    fn get_client_key_set(&self, _context: &mut C, _client_data: &ClientData)
+                          -> Result<Option<Box<KeySet>>, OAuthError>
+    {
+        Ok(None)
+    }
+
+    /// Record the `jti` of a client assertion with which `client_id` authenticated,
+    /// returning Ok(false) if it was seen before, in which case the assertion is
+    /// refused as a replay (RFC 7523 section 3).  Remember each `jti` until `exp`, which
+    /// is at most `client_authn_method::MAX_CLIENT_ASSERTION_LIFETIME` seconds after it
+    /// was issued.  This is called with `&self`, so you need interior mutability (such
+    /// as a `RefCell` or `Mutex`) or an external store to record it.
+    ///
+    /// The default implementation returns Ok(true), accepting every assertion within
+    /// its lifetime, so replays are not detected unless you implement this.
+    fn record_client_assertion_jti(&self, _context: &mut C, _client_id: &ClientId,
+                                   _jti: &str, _exp: u64) -> Result<bool, OAuthError>
+    {
+        Ok(true)
+    }
+
+    /// Fetch the request object which the client `client_id` published at
+    /// `request_uri` (RFC 9101 section 5.2.3).  To avoid being used to make requests
+    /// to arbitrary URLs, you should only fetch URLs the client has registered.
+    /// Return Ok(None) if the URL is not acceptable.
+    ///
+    /// The default implementation returns Ok(None), in which case only request objects
+    /// passed by value (in the `request` parameter) are supported.
+    fn fetch_request_object(&self, _context: &mut C, _client_id: &ClientId,
+                            _request_uri: &str) -> Result<Option<String>, OAuthError>
+    {
+        Ok(None)
+    }
+
+    /// Validate an access token issued by this Authorization Server, returning its
+    /// metadata, for requests made to the Authorization Server's own protected
+    /// resources such as the UserInfo endpoint.
+    ///
+    /// Return `TokenIntrospection::inactive()` if the token is unknown, expired or
+    /// revoked; return an error only if the token could not be checked, which the
+    /// UserInfo endpoint reports as 503 Service Unavailable.  The default
+    /// implementation returns inactive for every token.
+    fn validate_access_token(&self, _context: &mut C, _access_token: &str)
+                             -> Result<TokenIntrospection, OAuthError>
+    {
+        Ok(TokenIntrospection::inactive())
+    }
+
+    /// Supply the claims about the end-user `sub` for the UserInfo endpoint (OpenID
+    /// Connect Core section 5.3).  `scope` is the scope of the access token presented.
+    /// You may return every claim you have; those not granted by `scope` are filtered
+    /// out, and `sub` is added for you.
+    ///
+    /// An error is reported to the client as 503 Service Unavailable, so return one
+    /// only when the claims cannot be looked up, not for an unknown end-user.
+    ///
+    /// The default implementation returns no claims besides `sub`.
+    fn get_userinfo_claims(&mut self, _context: &mut C, _sub: &str, _scope: &str)
+                           -> Result<BTreeMap<String, Value>, OAuthError>
+    {
+        Ok(BTreeMap::new())
+    }
+
+    /// Get the metadata describing this Authorization Server as an OpenID Provider
+    /// (OpenID Connect Discovery), to be served by `handle_provider_metadata_request()`.
+    /// `ProviderMetadata::new()` is a good starting point.
+    ///
+    /// The default implementation returns None, publishing no metadata.
+    fn get_provider_metadata(&self, _context: &mut C) -> Option<ProviderMetadata> {
+        None
+    }
+
+    /// The issuer identifier of this Authorization Server, used as the `iss` claim of
+    /// tokens it signs.
+    ///
+    /// The default implementation takes it from `get_metadata()`, or failing that from
+    /// `get_provider_metadata()`.
+    fn get_issuer(&self, context: &mut C) -> Option<String> {
+        match self.get_metadata(context) {
+            Some(m) => Some(m.issuer),
+            None => self.get_provider_metadata(context).map(|m| m.oauth.issuer),
+        }
+    }
+
+    /// The key with which this Authorization Server signs tokens, such as ID tokens.
+    ///
+    /// The default implementation returns None, in which case ID tokens are not issued
+    /// and the `openid` scope is refused.
+    fn get_signing_key(&self, _context: &mut C) -> Option<&JwsKey> {
+        None
+    }
+
+    /// The set of keys whose public keys are published by `handle_jwks_request()`.
+    /// This should include the signing key, and during key rotation the retired keys
+    /// as long as tokens signed with them remain valid.
+    ///
+    /// The default implementation returns None, in which case only the signing key is
+    /// published.
+    fn get_key_set(&self, _context: &mut C) -> Option<&KeySet> {
+        None
+    }
+
+    /// The lifetime of ID tokens, in seconds.
+    ///
+    /// The default implementation returns 600.
+    fn get_id_token_lifetime(&self, _context: &mut C) -> u64 {
+        600
+    }
+
+    /// Supply the claims about the end-user who authorized `authorization`, for the ID
+    /// token issued with an access token when the `openid` scope was granted (OpenID
+    /// Connect Core section 2).  If `authorization.max_age` is set, you must supply
+    /// `auth_time`, or no tokens are issued.  The `nonce` is added for you.
+    ///
+    /// The default implementation returns `OAuthError::AuthzIdTokenUnsupported`.
+    fn get_id_token_claims(&mut self, _context: &mut C, _authorization: &ClientAuthorization)
+                           -> Result<IdTokenClaims, OAuthError>
+    {
+        Err(OAuthError::AuthzIdTokenUnsupported)
+    }
+
+    /// The URL of the client configuration endpoint (RFC 7592) for a newly registered
+    /// client, if you offer one.  If this returns Some, a registration access token is
+    /// issued with the registration, which the client can use at that URL to read,
+    /// update or delete its registration.  Route requests at that URL to
+    /// `handle_client_configuration_request()`.
+    ///
+    /// The default implementation returns None, offering no configuration endpoint.
+    fn get_registration_client_uri(&self, _context: &mut C, _client_id: &ClientId)
+                                   -> Option<String>
+    {
+        None
+    }
+
+    /// Store a newly registered client (RFC 7591).  `client_data` has been generated
+    /// from the validated metadata in `registration`, with a newly issued client
+    /// identifier and (for confidential clients) secret.  You should store both, so
+    /// that `fetch_client_data()` and `retrieve_client_registration()` will find the
+    /// client.  You may refuse the registration, e.g. for policy reasons, by returning
+    /// an error.
+    ///
+    /// The default implementation returns `OAuthError::AuthzRegistrationRefused`,
+    /// refusing every registration.
+    fn register_client(&mut self, _context: &mut C, _client_data: &ClientData,
+                       _registration: &ClientRegistration) -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzRegistrationRefused)
+    }
+
+    /// Retrieve the registration of a client, as stored by `register_client()` or
+    /// `update_client_registration()`, including its registration access token.
+    /// Return None if the client is unknown.
+    ///
+    /// The default implementation returns None for every client.
+    fn retrieve_client_registration(&self, _context: &mut C, _client_id: &ClientId)
+                                    -> Result<Option<ClientRegistration>, OAuthError>
+    {
+        Ok(None)
+    }
+
+    /// Determine whether a client may change its registration from `current` to
+    /// `updated`.  Redirect URIs in `updated` have already been validated, and if the
+    /// client left out `scope`, its current scope has been kept.
+    ///
+    /// The default implementation refuses a change to the client type, as client
+    /// secrets are neither issued nor revoked by an update, and refuses a scope which
+    /// is not a subset of the current one.  Override it to let clients widen their
+    /// scope.
+    fn allow_client_update(&self, _context: &mut C, current: &ClientData,
+                           updated: &ClientData) -> bool
+    {
+        let scope_allowed = match (current.scope.as_ref(), updated.scope.as_ref()) {
+            (None, _) => true,
+            (Some(cur), Some(upd)) => scope_is_subset(upd, cur),
+            (Some(_), None) => false,
+        };
+        current.client_type == updated.client_type && scope_allowed
+    }
+
+    /// Replace the stored registration of a client (RFC 7592 section 2.2), such that
+    /// `fetch_client_data()` returns `client_data` and `retrieve_client_registration()`
+    /// returns `registration`.
+    ///
+    /// The default implementation returns `OAuthError::AuthzRegistrationRefused`.
+    fn update_client_registration(&mut self, _context: &mut C, _client_data: &ClientData,
+                                  _registration: &ClientRegistration)
+                                  -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzRegistrationRefused)
+    }
+
+    /// Delete a client (RFC 7592 section 2.3).  You should also invalidate its
+    /// registration access token, and any grants and tokens issued to it.
+    ///
+    /// The default implementation returns `OAuthError::AuthzRegistrationRefused`, in
+    /// which case clients are told deletion is not supported.
+    fn delete_client_registration(&mut self, _context: &mut C, _client_id: &ClientId)
+                                  -> Result<(), OAuthError>
+    {
+        Err(OAuthError::AuthzRegistrationRefused)
+    }
+
     /// Handle an HTTP request at the authorization endpoint
     /// (From a user-agent, redirected by a client)
     ///
@@ -74,88 +707,50 @@ pub trait AuthzServer<C>
     ///     If denied, set error to AccessDenied and pass on to finish_authz_request().
     ///  4) If all went well, set authorization_code and pass on to finish_authz_request().
     ///
+    /// If the returned request has a `response_type` of `ResponseType::Token`, then use
+    /// `grant_implicit_authz_request()` and `deny_implicit_authz_request()` instead of
+    /// `grant_authz_request()` and `deny_authz_request()`.
+    ///
+    /// If the request has a `request_uri` parameter, the parameters pushed earlier to
+    /// `handle_pushed_authz_request()` are used instead (RFC 9126 section 4).  If it
+    /// has a request object, in the `request` parameter or referenced by `request_uri`,
+    /// only the parameters in the request object are used, once it is verified with
+    /// the client's keys (RFC 9101 section 6).
+    ///
     /// Refer to rfc6749 section 3.1 as to the requirements of the URL endpoint that
     /// performs this task (TLS, no fragment, support of GET with POST optional)
     fn handle_authz_request(&self, context: &mut C, uri_string: &str)
                             -> Result<(AuthzRequest, Option<AuthzError>), OAuthError>
     {
-        // Get expected (and optional) request parameters
-        let mut response_type: Option<String> = None; // required
-        let mut client_id: Option<ClientId> = None; // required
-        let mut redirect_uri: Option<RedirectUri> = None; // optional
-        let mut scope: Option<String> = None; // optional
-        let mut state: Option<String> = None; // recommended, used for CSRF prevention
         let url = try!( Url::parse( uri_string) );
-        for (key,val) in url.query_pairs() {
-            match &*key {
-                "client_id" => client_id = Some(ClientId(val.into_owned())),
-                "response_type" => response_type = Some(val.into_owned()),
-                "redirect_uri" => redirect_uri = Some(RedirectUri(val.into_owned())),
-                "scope" => scope = Some(val.into_owned()),
-                "state" => state = Some(val.into_owned()),
-                _ => {} // MUST ignore unknown parameters
-            }
-        }
+        let mut params: HashMap<String, String> = url.query_pairs()
+            .map(|(key, val)| (key.into_owned(), val.into_owned()))
+            .collect();
 
-        // FIXME -- IF redirect_uri IS MISSING
-        //       -- IF redirect_uri IS INVALID
-        //       -- IF redirect_uri IS MISMATCHED
-        //       -- IF client_id IS MISSING
-        //       -- IF client_id IS INVALID
-        //   Then inform user-agent directly, DO NOT redirect.
-
-
-        // Require `client_id`
-        let client_id = match client_id {
-            None => {
-                // rfc6749, section 4.1.2.1 paragraph 1: "If the request fails due to a
-                // missing, invalid, or mismatching redirection URI, or if the client
-                // identifier is missing or invalid, the authorization server SHOULD
-                // inform the resource owner of the error and MUST NOT automatically
-                // redirect the user-agent to the invalid redirection URI.
-                return Err(OAuthError::AuthzMissingClientId);
+        // Resolve a pushed authorization request (RFC 9126 section 4).  The pushed
+        // parameters replace those in the query string, except for client_id which
+        // must match.
+        let request_uri = params.get("request_uri").cloned();
+        let pushed = match request_uri {
+            Some(ref request_uri) if request_uri.starts_with(PUSHED_REQUEST_URI_PREFIX) => {
+                let pushed_params = match try!(self.retrieve_pushed_authz_request(
+                    context, &*request_uri))
+                {
+                    Some(p) => p,
+                    None => return Err(OAuthError::AuthzRequestUriInvalid),
+                };
+                if pushed_params.get("client_id") != params.get("client_id") {
+                    return Err(OAuthError::AuthzClientIdMismatch);
+                }
+                params = pushed_params;
+                true
             },
-            Some(cid) => cid
-        };
-
-        // Verify the `client_id` matches a known client
-        // (and fetch client_data for further use later on)
-        if let None = try!(self.fetch_client_data(context, &client_id))
-        {
-            // rfc6749, section 4.1.2.1 paragraph 1: "If the request fails due to a
-            // missing, invalid, or mismatching redirection URI, or if the client
-            // identifier is missing or invalid, the authorization server SHOULD
-            // inform the resource owner of the error and MUST NOT automatically
-            // redirect the user-agent to the invalid redirection URI.
-            return Err(OAuthError::AuthzUnknownClient);
+            _ => false,
         };
 
-        let mut error: Option<AuthzError> = None; // Error to pass through, if any
-
-        // Require `response_type` and check it
-        match response_type {
-            None => error = Some(AuthzError {
-                error: AuthzErrorCode::InvalidRequest,
-                error_description: Some("Missing `response_type` parameter.".to_owned()),
-                error_uri: None,
-                state: state.clone(),
-            }),
-            Some(rt) => if &*rt != "code" {
-                error = Some(AuthzError {
-                    error: AuthzErrorCode::UnsupportedResponseType,
-                    error_description: Some("Respose type must be `code`.".to_owned()),
-                    error_uri: None,
-                    state: state.clone(),
-                });
-            }
-        }
+        let signed = try!(resolve_request_object(self, context, &mut params));
 
-        Ok((AuthzRequest {
-            client_id: client_id,
-            redirect_uri: redirect_uri,
-            scope: scope,
-            state: state,
-        }, error))
+        parse_authz_request(self, context, &params, pushed, signed)
     }
 
     /// This resolves the redirect_uri by using the one from the request (you should
@@ -241,145 +836,653 @@ pub trait AuthzServer<C>
         Ok(())
     }
 
-    /// Handle an HTTP request at the token endpoint
-    /// (from a client directly, via POST only)
-    ///
-    /// Refer to rfc6749 section 3.2 as to the requirements of the URL endpoint that
-    /// performs this task (TLS, no fragment, must use POST)
-    fn handle_token_request(&mut self, context: &mut C,
-                            mut request: Request, mut response: Response)
+    /// This finishes an Authorization Request sequence using the implicit grant if you
+    /// have granted the request.  The access token is issued directly to the user-agent
+    /// in the redirect URI fragment (rfc6749 section 4.2.2).  Any refresh token in
+    /// `token` is not passed on, as the implicit grant MUST NOT issue refresh tokens.
+    fn grant_implicit_authz_request(&mut self, mut response: Response,
+                                    redirect_uri: &RedirectUri, token: &TokenData,
+                                    state: Option<String>) -> Result<(), OAuthError>
     {
-        // Start preparing the response, as we set some response data regardless
-        // of success or failure.
-        response.headers_mut().set(ContentType::json());
-        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
-        response.headers_mut().set(Pragma::NoCache);
+        // Start the redirect URL
+        let mut url = try!(Url::parse(&***redirect_uri));
 
-        // Authenticate the client using HTTP Basic Authorization
-        let basic: Basic = if let Some(&Authorization(ref basic)) =
-            request.headers.get::<Authorization<Basic>>()
-        {
-            basic.clone()
-        } else {
-            token_response_fail!(response, None, TokenErrorCode::InvalidClient,
-                                 Some("Authorization header missing"));
-        };
-        let (auth_client_id, authz_credentials): (ClientId, String) =
-            match ClientData::http_basic_authentication_deconstruct(basic) {
-                Ok(stuff) => stuff,
-                Err(_) => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                               Some("Authorization header failed UTF-8 check")),
-            };
+        // Put the token into the redirect url fragment
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("access_token", &*token.access_token)
+            .append_pair("token_type", &*token.token_type);
+        if let Some(expires_in) = token.expires_in {
+            serializer.append_pair("expires_in", &*format!("{}", expires_in));
+        }
+        if let Some(ref s) = token.scope {
+            serializer.append_pair("scope", s);
+        }
+        if let Some(ref s) = state {
+            serializer.append_pair("state", s);
+        }
+        url.set_fragment(Some(&*serializer.finish()));
 
-        let client_data = match self.fetch_client_data(context, &auth_client_id) {
-            Err(_) => token_response_fail!(response, None, TokenErrorCode::InvalidClient,
-                                           Some("No such client")),
-            Ok(v) => match v {
-                Some(cd) => cd,
-                None => token_response_fail!(response, None, TokenErrorCode::InvalidClient,
-                                             Some("No such client")),
-            }
+        // Do the redirect
+        response.headers_mut().set(Location(url.into_string()));
+        *response.status_mut() = StatusCode::Found;
+        let streaming_response = response.start().unwrap();
+        let _ = streaming_response.end();
+        Ok(())
+    }
+
+    /// This finishes an Authorization Request sequence using the implicit grant if you
+    /// have denied the request.  The error is passed in the redirect URI fragment
+    /// (rfc6749 section 4.2.2.1).
+    fn deny_implicit_authz_request(&mut self, mut response: Response,
+                                   redirect_uri: &RedirectUri, error: AuthzError)
+                                   -> Result<(), OAuthError>
+    {
+        // Start the redirect URL
+        let mut url = try!(Url::parse(&***redirect_uri));
+
+        // Put error details into the redirect url fragment
+        error.put_into_fragment(&mut url);
+
+        // Do the redirect
+        response.headers_mut().set(Location(url.into_string()));
+        *response.status_mut() = StatusCode::Found;
+        let streaming_response = response.start().unwrap();
+        let _ = streaming_response.end();
+        Ok(())
+    }
+
+    /// Handle an HTTP request at the token endpoint
+    /// (from a client directly, via POST only)
+    ///
+    /// Refer to rfc6749 section 3.2 as to the requirements of the URL endpoint that
+    /// performs this task (TLS, no fragment, must use POST)
+    ///
+    /// The `authorization_code` grant type is always supported.  The `refresh_token`
+    /// grant type is supported if you implement `retrieve_refresh_token()` and
+    /// `rotate_refresh_token()`.  The `client_credentials` grant type is supported if
+    /// you implement `issue_token_for_client_credentials()`.  The `password` grant type
+    /// is only supported for clients that `allow_password_grant()` returns true for.
+    /// The device code grant type is supported if you implement
+    /// `poll_device_authorization()`.  The token exchange grant type is supported if
+    /// you implement `decide_token_exchange()` and `issue_token_for_exchange()`.  The
+    /// JWT bearer grant type is supported if you implement `get_assertion_key_set()`
+    /// and `issue_token_for_jwt_assertion()`, and the SAML 2.0 bearer grant type if
+    /// you implement `validate_saml2_assertion()` and
+    /// `issue_token_for_saml2_assertion()`.  The CIBA grant type is supported if you
+    /// implement `poll_backchannel_authentication()`, for clients whose
+    /// `backchannel_delivery_mode` is poll or ping.
+    fn handle_token_request(&mut self, context: &mut C,
+                            mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body.  Which of these are
+        // expected depends upon the grant_type.
+        let pairs: Vec<(String, String)> = match read_form_pairs(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
         };
+        let params: HashMap<String, String> = pairs.iter().cloned().collect();
 
-        if authz_credentials != client_data.credentials {
-            response.headers_mut().set(WwwAuthenticate("Basic".to_owned()));
-            token_response_fail!(response, Some(StatusCode::Unauthorized),
-                                 TokenErrorCode::InvalidClient,
-                                 Some("Client credentials do not match"));
-        }
+        // Authenticate the client
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
 
-        // Fail if the request is bad
-        match request.uri {
-            RequestUri::AbsolutePath(_) => {},
-            _ => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                      Some("Only AbsolutePath URLs are allowed")),
+        // Dispatch on grant_type
+        let result = match params.get("grant_type").map(|gt| &**gt) {
+            None => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
+                                         Some("grant_type parameter must be supplied in body")),
+            Some("authorization_code") =>
+                authorization_code_grant(self, context, &client_data, &params),
+            Some("refresh_token") =>
+                refresh_token_grant(self, context, &client_data, &params),
+            Some("client_credentials") =>
+                client_credentials_grant(self, context, &client_data, &params),
+            Some("password") =>
+                password_grant(self, context, &client_data, &params),
+            Some(DEVICE_CODE_GRANT_TYPE) =>
+                device_code_grant(self, context, &client_data, &params),
+            Some(TOKEN_EXCHANGE_GRANT_TYPE) =>
+                token_exchange_grant(self, context, &client_data, &pairs),
+            Some(JWT_BEARER_GRANT_TYPE) =>
+                jwt_bearer_grant(self, context, &client_data, &params),
+            Some(SAML2_BEARER_GRANT_TYPE) =>
+                saml2_bearer_grant(self, context, &client_data, &params),
+            Some(CIBA_GRANT_TYPE) =>
+                ciba_grant(self, context, &client_data, &params),
+            Some(_) => token_response_fail!(response, None,
+                                            TokenErrorCode::UnsupportedGrantType),
+        };
+        let token = match result {
+            Ok(t) => t,
+            Err(e) => token_response_error!(response, None, e),
         };
 
-        // Read the body as it will contain the url-encoded parameters
-        let mut body: Vec<u8> = Vec::new();
-        if let Err(_) = request.read_to_end(&mut body) {
-            token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                 Some("Failed to read request body"));
+        // JSON-ify the response.
+        let body = token.as_json();
+
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP GET request for the Authorization Server Metadata document, which
+    /// must be served at `/.well-known/oauth-authorization-server` (with the path of the
+    /// issuer identifier, if any, appended) per RFC 8414 section 3.
+    fn handle_metadata_request(&self, context: &mut C, mut response: Response)
+    {
+        let metadata = match self.get_metadata(context) {
+            Some(m) => m,
+            None => {
+                *response.status_mut() = StatusCode::NotFound;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        let body = metadata.as_json();
+
+        response.headers_mut().set(ContentType::json());
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request at the device authorization endpoint
+    /// (from a client directly, via POST only)
+    ///
+    /// Refer to RFC 8628 section 3.1.  The client is authenticated as at the token
+    /// endpoint.  A device code and user code are issued and stored with
+    /// `store_device_authorization()`.
+    fn handle_device_authorization_request(&mut self, context: &mut C,
+                                           mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body
+        let params: HashMap<String, String> = match read_form_params(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
+        };
+
+        // Authenticate the client
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
+
+        let verification_uri = match self.get_device_verification_uri(context) {
+            Some(v) => v,
+            None => token_response_fail!(response, None, TokenErrorCode::UnauthorizedClient,
+                                         Some("The device authorization grant is not \
+                                               supported")),
+        };
+
+        // Check the requested scope against what the client is allowed
+        let scope: Option<String> = match requested_scope(&client_data, &params) {
+            Ok(s) => s,
+            Err(e) => token_response_error!(response, None, e),
+        };
+
+        let device_code = TextNonce::sized_urlsafe(32).unwrap().into_string();
+        let user_code = generate_user_code();
+        match self.store_device_authorization(context, &client_data.client_id,
+                                              scope.as_ref().map(|s| &**s),
+                                              &*device_code, &*user_code,
+                                              DEVICE_CODE_LIFETIME) {
+            Ok(()) => {},
+            Err(OAuthError::AuthzGrantTypeUnsupported) =>
+                token_response_fail!(response, None, TokenErrorCode::UnauthorizedClient,
+                                     Some("The device authorization grant is not supported")),
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
         }
-        let body = match String::from_utf8(body) {
-            Ok(b) => b,
-            Err(_) => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                           Some("Body did not pass UTF-8 check")),
+
+        let verification_uri_complete = Url::parse(&*verification_uri).ok().map(|mut url| {
+            url.query_pairs_mut().append_pair("user_code", &*user_code);
+            url.into_string()
+        });
+        let device_authorization = DeviceAuthorization {
+            device_code: device_code,
+            user_code: user_code,
+            verification_uri: verification_uri,
+            verification_uri_complete: verification_uri_complete,
+            expires_in: DEVICE_CODE_LIFETIME,
+            interval: Some(DEVICE_POLL_INTERVAL),
+        };
+        let body = device_authorization.as_json();
+
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request to the backchannel authentication endpoint
+    /// (from a client directly, via POST only)
+    ///
+    /// Refer to CIBA Core section 7.  The client must be confidential, and registered
+    /// with a `backchannel_delivery_mode`.  The user is notified with
+    /// `notify_backchannel_user()`, and the client is given an `auth_req_id` with which
+    /// to wait for the result.
+    fn handle_backchannel_authentication_request(&mut self, context: &mut C,
+                                                 mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body
+        let params: HashMap<String, String> = match read_form_params(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
         };
 
-        // Get expected (and optional) request parameters
-        let mut grant_type: Option<String> = None;
-        let mut code: Option<String> = None;
-        let mut redirect_uri: Option<RedirectUri> = None;
+        // Authenticate the client
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
 
-        let url = match Url::parse( &*format!("http://DUMMY?{}",body)) {
-            Ok(url) => url,
-            Err(_) => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                           Some("Unable to parse body as www-form-urlencoded")),
+        // CIBA Core section 4: only confidential clients may use backchannel
+        // authentication
+        let mode = match client_data.backchannel_delivery_mode {
+            Some(m) if client_data.client_type == ClientType::ConfidentialClient => m,
+            _ => token_response_fail!(response, None, TokenErrorCode::UnauthorizedClient,
+                                      Some("Client is not registered for backchannel \
+                                            authentication")),
         };
 
-        for (key,val) in url.query_pairs() {
-            match &*key {
-                "grant_type" => grant_type = Some(val.into_owned()),
-                "code" => code = Some(val.into_owned()),
-                "redirect_uri" => redirect_uri = Some(RedirectUri(val.into_owned())),
-                _ => {} // MUST ignore unknown parameters
+        let backchannel_request = match BackchannelAuthRequest::from_params(&params, mode) {
+            Ok(r) => r,
+            Err(e) => token_response_error!(response, None, e),
+        };
+        if let Some(ref allowed) = client_data.scope {
+            if ! scope_is_subset(&*backchannel_request.scope, allowed) {
+                token_response_fail!(response, None, TokenErrorCode::InvalidScope,
+                                     Some("scope exceeds the scope allowed for client"));
             }
         }
+        let expires_in = match backchannel_request.requested_expiry {
+            Some(e) if e < BACKCHANNEL_AUTH_LIFETIME => e,
+            _ => BACKCHANNEL_AUTH_LIFETIME,
+        };
 
-        // Require grant_type = "authorization_code"
-        match grant_type {
-            None => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                         Some("grant_type parameter must be supplied in body")),
-            Some(gt) => if gt != "authorization_code" {
-                token_response_fail!(response, None, TokenErrorCode::UnsupportedGrantType);
+        let auth_req_id = TextNonce::sized_urlsafe(32).unwrap().into_string();
+        match self.notify_backchannel_user(context, &client_data, &backchannel_request,
+                                           &*auth_req_id, expires_in) {
+            Ok(()) => {},
+            Err(OAuthError::AuthzGrantTypeUnsupported) =>
+                token_response_fail!(response, None, TokenErrorCode::UnauthorizedClient,
+                                     Some("Backchannel authentication is not supported")),
+            Err(OAuthError::AuthzUnknownUser) =>
+                token_response_fail!(response, None, TokenErrorCode::UnknownUserId),
+            Err(OAuthError::AuthzBindingMessageInvalid) =>
+                token_response_fail!(response, None, TokenErrorCode::InvalidBindingMessage),
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
             }
         }
 
-        // Require code, and retrieve the data we issued with the code
-        // This also verifies that the code is valid, and was issued to the
-        // client in question.
-        let (stored_client_id, stored_redirect_uri): (ClientId, RedirectUri) = match code {
-            None => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
-                                         Some("code parameter must be supplied in body")),
-            Some(ref c) => match self.retrieve_client_authorization(context, c)
-            {
-                Ok(pair) => pair,
-                _ => token_response_fail!(response, None, TokenErrorCode::InvalidGrant,
-                                          Some("Invalid authorization code")),
+        let backchannel_response = BackchannelAuthResponse {
+            auth_req_id: auth_req_id,
+            expires_in: expires_in,
+            interval: match mode {
+                DeliveryMode::Push => None,
+                _ => Some(BACKCHANNEL_POLL_INTERVAL),
+            },
+        };
+        let body = backchannel_response.as_json();
+
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// This finishes a backchannel authentication request, once the user has approved
+    /// it on their device (in which case pass the issued tokens as
+    /// `BackchannelAuthResult::Granted`) or denied it, or it has expired.  The outcome
+    /// is recorded with `record_backchannel_decision()`, and ping and push clients are
+    /// notified using the `client_notification_token` they sent with the request.  For
+    /// push clients, the ID token issued must carry the
+    /// `urn:openid:params:jwt:claim:auth_req_id` claim (CIBA Core section 10.3.1).
+    fn complete_backchannel_authentication(&mut self, context: &mut C, client_id: &ClientId,
+                                           auth_req_id: &str,
+                                           client_notification_token: Option<&str>,
+                                           result: BackchannelAuthResult)
+                                           -> Result<(), OAuthError>
+    {
+        try!(self.record_backchannel_decision(context, client_id, auth_req_id, &result));
+
+        let client_data = match try!(self.fetch_client_data(context, client_id)) {
+            Some(cd) => cd,
+            None => return Err(OAuthError::AuthzUnknownClient),
+        };
+        match (client_data.backchannel_delivery_mode, client_notification_token) {
+            (Some(DeliveryMode::Poll), _) | (None, _) => Ok(()),
+            (Some(_), Some(token)) =>
+                send_backchannel_notification(&client_data, token, auth_req_id, &result),
+            (Some(_), None) => Err(OAuthError::AuthzBadRequest),
+        }
+    }
+
+    /// Handle an HTTP request to the pushed authorization request endpoint
+    /// (from a client directly, via POST only)
+    ///
+    /// Refer to RFC 9126 section 2.  The client is authenticated as at the token
+    /// endpoint, and the authorization request parameters in the body are validated
+    /// and stored with `store_pushed_authz_request()`.  The client then sends the
+    /// user-agent to the authorization endpoint with just `client_id` and the returned
+    /// `request_uri`, which `handle_authz_request()` resolves.
+    fn handle_pushed_authz_request(&mut self, context: &mut C,
+                                   mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body
+        let mut params: HashMap<String, String> = match read_form_params(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
+        };
+
+        // Authenticate the client
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
+
+        // The request is made on behalf of the authenticated client, and cannot itself
+        // refer to a pushed request (RFC 9126 section 2.1)
+        if let Some(client_id) = params.get("client_id") {
+            if *client_id != *client_data.client_id {
+                authz_error_response(response, AuthzErrorCode::InvalidRequest,
+                                     "`client_id` does not match the authenticated client.");
+                return;
+            }
+        }
+        if params.contains_key("request_uri") {
+            authz_error_response(response, AuthzErrorCode::InvalidRequest,
+                                 "`request_uri` cannot be pushed.");
+            return;
+        }
+        params.insert("client_id".to_owned(), (*client_data.client_id).clone());
+
+        // The client's credentials are not authorization request parameters, and must
+        // not be stored with them
+        params.remove("client_secret");
+        params.remove("client_assertion");
+        params.remove("client_assertion_type");
+
+        // Validate the request now, so that errors reach the client rather than the
+        // user-agent.  The request object, if any, is stored as pushed and verified
+        // again when it is used.
+        let mut resolved = params.clone();
+        let signed = match resolve_request_object(self, context, &mut resolved) {
+            Ok(signed) => signed,
+            Err(e) => {
+                authz_error_response(response, AuthzErrorCode::InvalidRequestObject,
+                                     &*format!("{}", e));
+                return;
+            }
+        };
+        let authz_request = match parse_authz_request(self, context, &resolved, true,
+                                                      signed) {
+            Ok((_, Some(mut e))) => {
+                // There is no redirect for the state to protect
+                e.state = None;
+                *response.status_mut() = StatusCode::BadRequest;
+                let body = e.as_json();
+                let mut response = response.start().unwrap();
+                response.write_all(body.as_bytes()).unwrap();
+                let _ = response.end();
+                return;
+            },
+            Ok((r, None)) => r,
+            Err(e) => {
+                authz_error_response(response, AuthzErrorCode::InvalidRequest,
+                                     &*format!("{}", e));
+                return;
             }
         };
+        if self.resolve_redirect_uri(context, &client_data.client_id,
+                                     authz_request.redirect_uri.as_ref()).is_err()
+        {
+            authz_error_response(response, AuthzErrorCode::InvalidRequest,
+                                 "`redirect_uri` is not registered.");
+            return;
+        }
 
-        // Verify the client_id matches
-        if stored_client_id != client_data.client_id {
-            // FIXME: also delete the stored code and related tokens.
-            token_response_fail!(response, None, TokenErrorCode::InvalidGrant,
-                                 Some("client_id mismatch"));
+        let request_uri = format!("{}{}", PUSHED_REQUEST_URI_PREFIX,
+                                  TextNonce::sized_urlsafe(32).unwrap().into_string());
+        match self.store_pushed_authz_request(context, &*request_uri, &params,
+                                              PUSHED_REQUEST_LIFETIME) {
+            Ok(()) => {},
+            Err(OAuthError::AuthzPushedRequestUnsupported) => {
+                authz_error_response(response, AuthzErrorCode::InvalidRequest,
+                                     "Pushed authorization requests are not supported.");
+                return;
+            },
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
         }
 
-        // Verify the redirect_uri matches, if it was used originally
-        if redirect_uri.is_some() {
-            match redirect_uri {
-                None => token_response_fail!(response, None, TokenErrorCode::InvalidGrant,
-                                             Some("redirect_uri parameter must be \
-                                                   supplied in body")),
-                Some(ru) => if &ru != &stored_redirect_uri {
-                    token_response_fail!(response, None, TokenErrorCode::InvalidGrant,
-                                         Some("redirect_uri parameter mismatch"));
-                }
+        let body = format!("{{\r\n  \"request_uri\": {},\r\n  \"expires_in\": {}\r\n}}",
+                           ::serde_json::to_string(&request_uri).unwrap(),
+                           PUSHED_REQUEST_LIFETIME);
+
+        *response.status_mut() = StatusCode::Created;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request for the OpenID Provider Metadata
+    /// (from a relying party directly, via GET only)
+    ///
+    /// Serve this at the URL given by `ProviderMetadata::well_known_url()`.  Responds
+    /// with 404 Not Found if `get_provider_metadata()` returns None.
+    fn handle_provider_metadata_request(&self, context: &mut C, mut response: Response)
+    {
+        let metadata = match self.get_provider_metadata(context) {
+            Some(m) => m,
+            None => {
+                *response.status_mut() = StatusCode::NotFound;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        let body = metadata.as_json();
+
+        response.headers_mut().set(ContentType::json());
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request for the JSON Web Key Set (RFC 7517) at the `jwks_uri`
+    /// (from anyone, via GET only)
+    ///
+    /// The public keys of `get_key_set()` are published, or if that returns None, the
+    /// public key of `get_signing_key()`.  Shared secrets are never published.
+    fn handle_jwks_request(&self, context: &mut C, mut response: Response)
+    {
+        let jwks = match self.get_key_set(context) {
+            Some(key_set) => key_set.public_jwks(),
+            None => self.get_signing_key(context).and_then(|k| k.public_jwk())
+                .into_iter().collect(),
+        };
+
+        let body = jwk_set_json(&*jwks);
+
+        response.headers_mut().set(ContentType::json());
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request at the token revocation endpoint
+    /// (from a client directly, via POST only)
+    ///
+    /// Refer to RFC 7009 section 2 as to the requirements of the URL endpoint that
+    /// performs this task (TLS, must use POST).  Clients authenticate in the same way
+    /// as at the token endpoint.
+    fn handle_revocation_request(&mut self, context: &mut C,
+                                 mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body
+        let params: HashMap<String, String> = match read_form_params(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
+        };
+
+        // Authenticate the client
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
+
+        // Require token
+        let token: &String = match params.get("token") {
+            None => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
+                                         Some("token parameter must be supplied in body")),
+            Some(t) => t,
+        };
+
+        // An unknown token_type_hint is ignored (RFC 7009 section 2.1)
+        let token_type_hint = params.get("token_type_hint")
+            .and_then(|h| TokenTypeHint::from_str(h));
+
+        match self.revoke_token(context, &client_data.client_id, token, token_type_hint) {
+            Ok(()) => {},
+            Err(OAuthError::AuthzTokenTypeUnsupported) =>
+                token_response_fail!(response, None, TokenErrorCode::UnsupportedTokenType),
+            Err(_) => {
+                // RFC 7009 section 2.2.1: the client may retry after a 503
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
             }
         }
 
-        // Issue token
-        let token = match self.issue_token_to_client(context, code.as_ref().unwrap(),
-                                                     &client_data.client_id) {
-            Ok(t) => t,
-            Err(_) => token_response_fail!(response, None, TokenErrorCode::InvalidGrant,
-                                           None),
+        // RFC 7009 section 2.2: "The content of the response body is ignored by the
+        // client as all necessary information is conveyed in the response code."
+        *response.status_mut() = StatusCode::Ok;
+        let response = response.start().unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request at the token introspection endpoint
+    /// (from a protected resource directly, via POST only)
+    ///
+    /// Refer to RFC 7662 section 2 as to the requirements of the URL endpoint that
+    /// performs this task (TLS, must use POST).  Protected resources must be registered
+    /// as confidential clients, and authenticate in the same way as clients at the token
+    /// endpoint.  Public clients are refused.
+    fn handle_introspection_request(&mut self, context: &mut C,
+                                    mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the url-encoded parameters from the body
+        let params: HashMap<String, String> = match read_form_params(&mut request) {
+            Ok(p) => p,
+            Err(e) => token_response_error!(response, None, e),
         };
 
-        // JSON-ify the response.
-        let body = token.as_json();
+        // Authenticate the protected resource.  Public clients only identify
+        // themselves, so may not introspect tokens.
+        let client_data = match authenticate_client(self, context, &request, &params) {
+            Ok(cd) => cd,
+            Err(e) => client_authentication_fail!(request, response, e),
+        };
+        if client_data.authn_method == ClientAuthnMethod::None {
+            token_response_fail!(response, Some(StatusCode::Unauthorized),
+                                 TokenErrorCode::InvalidClient,
+                                 Some("Public clients may not introspect tokens"));
+        }
+
+        // Require token
+        let token: &String = match params.get("token") {
+            None => token_response_fail!(response, None, TokenErrorCode::InvalidRequest,
+                                         Some("token parameter must be supplied in body")),
+            Some(t) => t,
+        };
+
+        // An unknown token_type_hint is ignored (RFC 7662 section 2.1)
+        let token_type_hint = params.get("token_type_hint")
+            .and_then(|h| TokenTypeHint::from_str(h));
+
+        let introspection = match self.introspect_token(context, &client_data.client_id,
+                                                        token, token_type_hint)
+        {
+            Ok(i) => i,
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        // Inactive tokens reveal nothing else (RFC 7662 section 2.2)
+        let body = if introspection.active {
+            introspection.as_json()
+        } else {
+            TokenIntrospection::inactive().as_json()
+        };
 
         *response.status_mut() = StatusCode::Ok;
 
@@ -387,5 +1490,1304 @@ pub trait AuthzServer<C>
         response.write_all(body.as_bytes()).unwrap();
         let _ = response.end();
     }
-}
 
+    /// Handle an HTTP request at the UserInfo endpoint
+    /// (from a client directly, via GET or POST)
+    ///
+    /// Refer to OpenID Connect Core section 5.3.  The request must present a bearer
+    /// access token (RFC 6750) that was granted the `openid` scope.  The claims
+    /// returned are those from `get_userinfo_claims()` which the `profile`, `email`,
+    /// `address` and `phone` scopes of the access token grant.
+    fn handle_userinfo_request(&mut self, context: &mut C,
+                               mut request: Request, mut response: Response)
+    {
+        let claims = match userinfo_claims(self, context, &mut request) {
+            Ok(c) => c,
+            Err(e) => {
+                response.headers_mut().set(WwwAuthenticate(e.as_challenge(None)));
+                *response.status_mut() = e.status();
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        let body = ::serde_json::to_string(&claims).unwrap();
+
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request at the client registration endpoint
+    /// (from a client or developer directly, via POST only)
+    ///
+    /// Refer to RFC 7591 section 3 as to the requirements of the URL endpoint that
+    /// performs this task (TLS, must use POST with a JSON body).  If you require an
+    /// initial access token to register, check it before calling this function.
+    fn handle_registration_request(&mut self, context: &mut C,
+                                   mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Read the body as it will contain the JSON client metadata
+        let mut body: Vec<u8> = Vec::new();
+        if let Err(_) = request.read_to_end(&mut body) {
+            registration_response_fail!(response, RegistrationErrorCode::InvalidClientMetadata,
+                                        Some("Failed to read request body"));
+        }
+        let body = match String::from_utf8(body) {
+            Ok(b) => b,
+            Err(_) => registration_response_fail!(
+                response, RegistrationErrorCode::InvalidClientMetadata,
+                Some("Body did not pass UTF-8 check")),
+        };
+        let metadata: ClientMetadata = match ::serde_json::from_str(&*body) {
+            Ok(m) => m,
+            Err(_) => registration_response_fail!(
+                response, RegistrationErrorCode::InvalidClientMetadata,
+                Some("Unable to parse body as client metadata")),
+        };
+
+        // Validate the metadata, filling in defaults
+        let metadata = match metadata.validated() {
+            Ok(m) => m,
+            Err(e) => registration_response_fail!(
+                response, e.error, e.error_description.as_ref().map(|s| &**s)),
+        };
+
+        // Issue the client identifier, and a secret for confidential clients
+        let client_id = ClientId(TextNonce::sized_urlsafe(32).unwrap().into_string());
+        let client_secret: Option<String> = match metadata.client_type() {
+            ClientType::ConfidentialClient =>
+                Some(TextNonce::sized_urlsafe(48).unwrap().into_string()),
+            ClientType::PublicClient => None,
+        };
+        let client_data = metadata.to_client_data(&client_id,
+                                                  client_secret.as_ref().map(|s| &**s));
+
+        // Issue a registration access token if there is a configuration endpoint
+        let registration_client_uri = self.get_registration_client_uri(context, &client_id);
+        let registration_access_token = registration_client_uri.as_ref().map(
+            |_| TextNonce::sized_urlsafe(32).unwrap().into_string());
+
+        let registration = ClientRegistration {
+            client_id: client_id,
+            client_secret_expires_at: client_secret.as_ref().map(|_| 0),
+            client_secret: client_secret,
+            client_id_issued_at: Some(::time::get_time().sec as u64),
+            registration_access_token: registration_access_token,
+            registration_client_uri: registration_client_uri,
+            metadata: metadata,
+        };
+
+        if let Err(_) = self.register_client(context, &client_data, &registration) {
+            registration_response_fail!(response, RegistrationErrorCode::InvalidClientMetadata,
+                                        Some("Client registration was refused"));
+        }
+
+        let body = registration.as_json();
+
+        *response.status_mut() = StatusCode::Created;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+
+    /// Handle an HTTP request at the client configuration endpoint of `client_id`
+    /// (from a client directly, via GET, PUT or DELETE)
+    ///
+    /// Refer to RFC 7592 section 2.  The client must present the registration access
+    /// token issued with its registration as a bearer token.  GET reads the
+    /// registration, PUT replaces the client metadata (subject to
+    /// `allow_client_update()`), and DELETE deletes the client.
+    fn handle_client_configuration_request(&mut self, context: &mut C,
+                                           client_id: &ClientId,
+                                           mut request: Request, mut response: Response)
+    {
+        // Start preparing the response, as we set some response data regardless
+        // of success or failure.
+        response.headers_mut().set(ContentType::json());
+        response.headers_mut().set(CacheControl(vec![ CacheDirective::NoStore ]));
+        response.headers_mut().set(Pragma::NoCache);
+
+        // Unknown clients are treated as invalid registration access tokens
+        // (RFC 7592 section 3)
+        let registration = match self.retrieve_client_registration(context, client_id) {
+            Ok(Some(r)) => r,
+            Ok(None) => return configuration_unauthorized(response),
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+        let authentic = match (request.headers.get::<Authorization<Bearer>>(),
+                               registration.registration_access_token.as_ref()) {
+            (Some(&Authorization(ref bearer)), Some(expected)) =>
+                bearer.token.len() == expected.len() &&
+                memcmp::eq(bearer.token.as_bytes(), expected.as_bytes()),
+            _ => false,
+        };
+        if ! authentic {
+            return configuration_unauthorized(response);
+        }
+        let current = match self.fetch_client_data(context, client_id) {
+            Ok(Some(cd)) => cd,
+            Ok(None) => return configuration_unauthorized(response),
+            Err(_) => {
+                *response.status_mut() = StatusCode::ServiceUnavailable;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        let method = request.method.clone();
+        let registration = match method {
+            Method::Get => registration,
+            Method::Put => {
+                let mut body = String::new();
+                if let Err(_) = request.read_to_string(&mut body) {
+                    registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("Failed to read request body"));
+                }
+                let value: ::serde_json::Value = match ::serde_json::from_str(&*body) {
+                    Ok(v) => v,
+                    Err(_) => registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("Unable to parse body as client metadata")),
+                };
+
+                // The request must include the client_id, and any client_secret
+                // included must match (RFC 7592 section 2.2)
+                if value.find("client_id").and_then(|v| v.as_string()) != Some(&***client_id) {
+                    registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("client_id does not match"));
+                }
+                if let Some(secret) = value.find("client_secret") {
+                    if secret.as_string() != registration.client_secret.as_ref().map(|s| &**s) {
+                        registration_response_fail!(
+                            response, RegistrationErrorCode::InvalidClientMetadata,
+                            Some("client_secret does not match"));
+                    }
+                }
+
+                let metadata: ClientMetadata = match ::serde_json::from_value(value) {
+                    Ok(m) => m,
+                    Err(_) => registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("Unable to parse body as client metadata")),
+                };
+                let mut metadata = match metadata.validated() {
+                    Ok(m) => m,
+                    Err(e) => registration_response_fail!(
+                        response, e.error, e.error_description.as_ref().map(|s| &**s)),
+                };
+                // Leaving out scope must not lift the client's scope restriction
+                if metadata.scope.is_none() {
+                    metadata.scope = current.scope.clone();
+                }
+                // Whether the client must use pushed authorization requests or signed
+                // request objects is for the Authorization Server to decide, so the
+                // client cannot change it
+                metadata.require_pushed_authorization_requests = Some(current.par_required);
+                metadata.require_signed_request_object =
+                    Some(current.request_object_required);
+
+                let updated = metadata.to_client_data(
+                    client_id, registration.client_secret.as_ref().map(|s| &**s));
+                if ! self.allow_client_update(context, &current, &updated) {
+                    registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("The requested changes are not allowed"));
+                }
+
+                let registration = ClientRegistration {
+                    metadata: metadata,
+                    .. registration
+                };
+                if let Err(_) = self.update_client_registration(context, &updated,
+                                                                &registration) {
+                    registration_response_fail!(
+                        response, RegistrationErrorCode::InvalidClientMetadata,
+                        Some("Client update was refused"));
+                }
+                registration
+            },
+            Method::Delete => {
+                // RFC 7592 section 2.3: 204 on success, 405 if deletion is unsupported
+                *response.status_mut() = match self.delete_client_registration(context,
+                                                                               client_id) {
+                    Ok(()) => StatusCode::NoContent,
+                    Err(OAuthError::AuthzRegistrationRefused) => StatusCode::MethodNotAllowed,
+                    Err(_) => StatusCode::ServiceUnavailable,
+                };
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            },
+            _ => {
+                *response.status_mut() = StatusCode::MethodNotAllowed;
+                let response = response.start().unwrap();
+                let _ = response.end();
+                return;
+            }
+        };
+
+        let body = registration.as_json();
+
+        *response.status_mut() = StatusCode::Ok;
+
+        let mut response = response.start().unwrap();
+        response.write_all(body.as_bytes()).unwrap();
+        let _ = response.end();
+    }
+}
+
+
+/// Replace the parameters of an authorization request with those of its request
+/// object, passed by value or by reference (RFC 9101 section 6).  Returns whether there
+/// was a request object.
+fn resolve_request_object<C, S: AuthzServer<C> + ?Sized>(
+    server: &S, context: &mut C, params: &mut HashMap<String, String>)
+    -> Result<bool, OAuthError>
+{
+    let client_id = match params.get("client_id") {
+        Some(c) => ClientId(c.clone()),
+        None => return Err(OAuthError::AuthzMissingClientId),
+    };
+    let request = match (params.get("request").cloned(), params.get("request_uri").cloned()) {
+        (None, None) => return Ok(false),
+        (Some(request), None) => request,
+        (None, Some(request_uri)) => {
+            match try!(server.fetch_request_object(context, &client_id, &*request_uri)) {
+                Some(request) => request,
+                None => return Err(OAuthError::AuthzRequestUriInvalid),
+            }
+        },
+        (Some(_), Some(_)) => return Err(OAuthError::AuthzBadRequest),
+    };
+
+    let client_data = match try!(server.fetch_client_data(context, &client_id)) {
+        Some(cd) => cd,
+        None => return Err(OAuthError::AuthzUnknownClient),
+    };
+    let key_set = match try!(server.get_client_key_set(context, &client_data)) {
+        Some(k) => k,
+        None => return Err(OAuthError::AuthzRequestObjectInvalid),
+    };
+    let issuer = server.get_issuer(context);
+
+    // Only the parameters in the request object are used, even if the same parameters
+    // are in the query string (RFC 9101 section 6.3).  Clients which must sign their
+    // requests must also bind them to this server and limit their lifetime.
+    *params = try!(verify_request_object(&*request, &client_id, &*key_set,
+                                         issuer.as_ref().map(|s| &**s),
+                                         client_data.request_object_required));
+    Ok(true)
+}
+
+/// Parse and validate the parameters of an authorization request, which were either
+/// in the query string or pushed (RFC 9126), and which may have been signed (RFC 9101).
+/// Returns the request and any error to pass on to the client.
+fn parse_authz_request<C, S: AuthzServer<C> + ?Sized>(
+    server: &S, context: &mut C, params: &HashMap<String, String>, pushed: bool,
+    signed: bool)
+    -> Result<(AuthzRequest, Option<AuthzError>), OAuthError>
+{
+    // Get expected (and optional) request parameters
+    let response_type: Option<String> = params.get("response_type").cloned(); // required
+    let client_id: Option<ClientId> = params.get("client_id")
+        .map(|c| ClientId(c.clone())); // required
+    let redirect_uri: Option<RedirectUri> = params.get("redirect_uri")
+        .map(|r| RedirectUri(r.clone())); // optional
+    let scope: Option<String> = params.get("scope").cloned(); // optional
+    let state: Option<String> = params.get("state").cloned(); // recommended, for CSRF
+    let code_challenge: Option<String> = params.get("code_challenge").cloned(); // RFC 7636
+    let code_challenge_method: Option<String> =
+        params.get("code_challenge_method").cloned(); // RFC 7636
+    let nonce: Option<String> = params.get("nonce").cloned(); // OpenID Connect
+    let prompt: Option<String> = params.get("prompt").cloned(); // OpenID Connect
+    let max_age: Option<String> = params.get("max_age").cloned(); // OpenID Connect
+    let login_hint: Option<String> = params.get("login_hint").cloned(); // OpenID Connect
+
+    // FIXME -- IF redirect_uri IS MISSING
+    //       -- IF redirect_uri IS INVALID
+    //       -- IF redirect_uri IS MISMATCHED
+    //       -- IF client_id IS MISSING
+    //       -- IF client_id IS INVALID
+    //   Then inform user-agent directly, DO NOT redirect.
+
+
+    // Require `client_id`
+    let client_id = match client_id {
+        None => {
+            // rfc6749, section 4.1.2.1 paragraph 1: "If the request fails due to a
+            // missing, invalid, or mismatching redirection URI, or if the client
+            // identifier is missing or invalid, the authorization server SHOULD
+            // inform the resource owner of the error and MUST NOT automatically
+            // redirect the user-agent to the invalid redirection URI.
+            return Err(OAuthError::AuthzMissingClientId);
+        },
+        Some(cid) => cid
+    };
+
+    // Verify the `client_id` matches a known client
+    // (and fetch client_data for further use later on)
+    let client_data = match try!(server.fetch_client_data(context, &client_id))
+    {
+        Some(cd) => cd,
+        None => {
+            // rfc6749, section 4.1.2.1 paragraph 1: "If the request fails due to a
+            // missing, invalid, or mismatching redirection URI, or if the client
+            // identifier is missing or invalid, the authorization server SHOULD
+            // inform the resource owner of the error and MUST NOT automatically
+            // redirect the user-agent to the invalid redirection URI.
+            return Err(OAuthError::AuthzUnknownClient);
+        }
+    };
+
+    let mut error: Option<AuthzError> = None; // Error to pass through, if any
+
+    // Require a pushed authorization request, if the client must use them
+    // (RFC 9126 section 6)
+    if client_data.par_required && ! pushed {
+        error = Some(AuthzError {
+            error: AuthzErrorCode::InvalidRequest,
+            error_description: Some("Pushed authorization request required.".to_owned()),
+            error_uri: None,
+            state: state.clone(),
+        });
+    }
+
+    // Require a signed request object, if the client must use them
+    // (RFC 9101 section 10.5)
+    if client_data.request_object_required && ! signed {
+        error = Some(AuthzError {
+            error: AuthzErrorCode::InvalidRequest,
+            error_description: Some("Signed request object required.".to_owned()),
+            error_uri: None,
+            state: state.clone(),
+        });
+    }
+
+    // Require `response_type` and check it
+    let response_type = match response_type {
+        None => {
+            error = Some(AuthzError {
+                error: AuthzErrorCode::InvalidRequest,
+                error_description: Some("Missing `response_type` parameter.".to_owned()),
+                error_uri: None,
+                state: state.clone(),
+            });
+            ResponseType::Code
+        },
+        Some(rt) => match &*rt {
+            "code" => ResponseType::Code,
+            "token" => {
+                if ! server.allow_implicit_grant(context, &client_data) {
+                    error = Some(AuthzError {
+                        error: AuthzErrorCode::UnauthorizedClient,
+                        error_description: Some("Client is not permitted to use \
+                                                 the implicit grant.".to_owned()),
+                        error_uri: None,
+                        state: state.clone(),
+                    });
+                }
+                ResponseType::Token
+            },
+            _ => {
+                error = Some(AuthzError {
+                    error: AuthzErrorCode::UnsupportedResponseType,
+                    error_description: Some("Respose type must be `code` or \
+                                             `token`.".to_owned()),
+                    error_uri: None,
+                    state: state.clone(),
+                });
+                ResponseType::Code
+            }
+        }
+    };
+
+    // Check the PKCE code challenge (RFC 7636 section 4.4.1)
+    let code_challenge = match (code_challenge, code_challenge_method) {
+        (None, None) => {
+            if error.is_none() && response_type == ResponseType::Code &&
+                server.require_pkce(context, &client_data)
+            {
+                error = Some(AuthzError {
+                    error: AuthzErrorCode::InvalidRequest,
+                    error_description: Some("Code challenge required.".to_owned()),
+                    error_uri: None,
+                    state: state.clone(),
+                });
+            }
+            None
+        },
+        (None, Some(_)) => {
+            if error.is_none() {
+                error = Some(AuthzError {
+                    error: AuthzErrorCode::InvalidRequest,
+                    error_description: Some("Missing `code_challenge` parameter.".to_owned()),
+                    error_uri: None,
+                    state: state.clone(),
+                });
+            }
+            None
+        },
+        (Some(cc), method) => {
+            let method = match method {
+                None => Some(CodeChallengeMethod::Plain),
+                Some(m) => CodeChallengeMethod::from_str(&*m),
+            };
+            match method {
+                Some(m) if valid_code_challenge_str(&*cc) => Some(CodeChallenge {
+                    challenge: cc,
+                    method: m,
+                }),
+                _ => {
+                    if error.is_none() {
+                        error = Some(AuthzError {
+                            error: AuthzErrorCode::InvalidRequest,
+                            error_description: Some("Invalid `code_challenge` or \
+                                                     unsupported `code_challenge_method`."
+                                                    .to_owned()),
+                            error_uri: None,
+                            state: state.clone(),
+                        });
+                    }
+                    None
+                }
+            }
+        }
+    };
+
+    // Check the OpenID Connect prompt and max_age (OpenID Connect Core section
+    // 3.1.2.1)
+    let prompt: Vec<String> = prompt.as_ref().map(|p| {
+        p.split(' ').filter(|v| ! v.is_empty()).map(|v| v.to_owned()).collect()
+    }).unwrap_or(vec![]);
+    let prompt_valid = prompt.iter().all(|v| match &**v {
+        "none" | "login" | "consent" | "select_account" => true,
+        _ => false,
+    }) && ! (prompt.len() > 1 && prompt.iter().any(|v| v == "none"));
+    if ! prompt_valid && error.is_none() {
+        error = Some(AuthzError {
+            error: AuthzErrorCode::InvalidRequest,
+            error_description: Some("Invalid `prompt` parameter.".to_owned()),
+            error_uri: None,
+            state: state.clone(),
+        });
+    }
+    let max_age: Option<u64> = match max_age {
+        None => None,
+        Some(ma) => match ma.parse::<u64>() {
+            Ok(ma) => Some(ma),
+            Err(_) => {
+                if error.is_none() {
+                    error = Some(AuthzError {
+                        error: AuthzErrorCode::InvalidRequest,
+                        error_description: Some("Invalid `max_age` parameter.".to_owned()),
+                        error_uri: None,
+                        state: state.clone(),
+                    });
+                }
+                None
+            }
+        }
+    };
+
+    Ok((AuthzRequest {
+        response_type: response_type,
+        client_id: client_id,
+        redirect_uri: redirect_uri,
+        scope: scope,
+        code_challenge: code_challenge,
+        state: state,
+        nonce: nonce,
+        prompt: prompt,
+        max_age: max_age,
+        login_hint: login_hint,
+    }, error))
+}
+
+/// Read the parameters of a request made directly to the AuthzServer (e.g. at the token
+/// endpoint), which are url-encoded in the body.  Unknown parameters are included, as
+/// the caller MUST ignore them.
+fn read_form_params(request: &mut Request) -> Result<HashMap<String, String>, TokenError>
+{
+    Ok(try!(read_form_pairs(request)).into_iter().collect())
+}
+
+/// Read the url-encoded parameters from the body of a request, in order and keeping
+/// repeated parameters (such as the `audience` and `resource` of RFC 8693)
+fn read_form_pairs(request: &mut Request) -> Result<Vec<(String, String)>, TokenError>
+{
+    // Fail if the request is bad
+    match request.uri {
+        RequestUri::AbsolutePath(_) => {},
+        _ => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                    Some("Only AbsolutePath URLs are allowed"))),
+    };
+
+    // Read the body as it will contain the url-encoded parameters
+    let mut body: Vec<u8> = Vec::new();
+    if let Err(_) = request.read_to_end(&mut body) {
+        return Err(token_error(TokenErrorCode::InvalidRequest,
+                               Some("Failed to read request body")));
+    }
+    let body = match String::from_utf8(body) {
+        Ok(b) => b,
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                         Some("Body did not pass UTF-8 check"))),
+    };
+
+    let url = match Url::parse( &*format!("http://DUMMY?{}",body)) {
+        Ok(url) => url,
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                         Some("Unable to parse body as www-form-urlencoded"))),
+    };
+
+    Ok(url.query_pairs()
+       .map(|(key,val)| (key.into_owned(), val.into_owned()))
+       .collect())
+}
+
+/// Authenticate a client making a request directly to the AuthzServer (e.g. at the
+/// token endpoint), returning the authenticated client's data.  The client must use
+/// the method in its `ClientData.authn_method`: a JWT client assertion (rfc7523
+/// section 2.2), its secret in HTTP Basic Authorization (client_secret_basic) or in
+/// the request body (client_secret_post) (rfc6749 section 2.3.1), or, for a public
+/// client, just its client_id parameter (none).
+fn authenticate_client<C, S: AuthzServer<C> + ?Sized>(
+    server: &S, context: &mut C, request: &Request, params: &HashMap<String, String>)
+    -> Result<ClientData, TokenError>
+{
+    // JWT client assertions (rfc7523 section 2.2) are used in place of the client secret
+    if params.contains_key("client_assertion_type") {
+        return authenticate_client_assertion(server, context, params);
+    }
+
+    // Otherwise the client authenticates with its secret, in the Authorization header or
+    // in the request body (rfc6749 section 2.3.1), or if it is a public client just
+    // identifies itself with the client_id parameter (rfc6749 section 3.2.1)
+    let basic = request.headers.get::<Authorization<Basic>>()
+        .map(|&Authorization(ref basic)| basic.clone());
+    let (client_id, secret, method): (ClientId, Option<String>, ClientAuthnMethod) =
+        match (basic, params.get("client_id")) {
+            (Some(basic), _) => {
+                if params.contains_key("client_secret") {
+                    return Err(token_error(TokenErrorCode::InvalidRequest,
+                                           Some("Multiple client authentication methods")));
+                }
+                match ClientData::http_basic_authentication_deconstruct(basic) {
+                    Ok((cid, secret)) => (cid, Some(secret), ClientAuthnMethod::ClientSecretBasic),
+                    Err(_) => return Err(token_error(
+                        TokenErrorCode::InvalidRequest,
+                        Some("Authorization header failed UTF-8 check"))),
+                }
+            },
+            (None, Some(cid)) => match params.get("client_secret") {
+                Some(secret) => (ClientId(cid.clone()), Some(secret.clone()),
+                                 ClientAuthnMethod::ClientSecretPost),
+                None => (ClientId(cid.clone()), None, ClientAuthnMethod::None),
+            },
+            (None, None) => return Err(token_error(TokenErrorCode::InvalidClient,
+                                                   Some("Client authentication required"))),
+        };
+
+    let client_data = match server.fetch_client_data(context, &client_id) {
+        Ok(Some(cd)) => cd,
+        _ => return Err(token_error(TokenErrorCode::InvalidClient, Some("No such client"))),
+    };
+
+    // Each client may only use the method it registered
+    if client_data.authn_method != method {
+        return Err(token_error(TokenErrorCode::InvalidClient,
+                               Some(&*format!("Client must authenticate with {}",
+                                              client_data.authn_method))));
+    }
+
+    match secret {
+        None if client_data.client_type == ClientType::PublicClient => {},
+        None => return Err(token_error(TokenErrorCode::InvalidClient,
+                                       Some("Client authentication required"))),
+        Some(ref secret) => {
+            if secret.len() != client_data.credentials.len() ||
+                ! memcmp::eq(secret.as_bytes(), client_data.credentials.as_bytes())
+            {
+                return Err(token_error(TokenErrorCode::InvalidClient,
+                                       Some("Client credentials do not match")));
+            }
+        },
+    }
+
+    Ok(client_data)
+}
+
+/// Authenticate a client by the JWT client assertion in the request parameters
+/// (rfc7523 section 2.2), which the client signs with its secret or with one of its keys
+/// according to its registered authentication method.
+fn authenticate_client_assertion<C, S: AuthzServer<C> + ?Sized>(
+    server: &S, context: &mut C, params: &HashMap<String, String>)
+    -> Result<ClientData, TokenError>
+{
+    if params.get("client_assertion_type").map(|t| &**t) != Some(CLIENT_ASSERTION_TYPE) {
+        return Err(token_error(TokenErrorCode::InvalidClient,
+                               Some("Unsupported client_assertion_type")));
+    }
+    let assertion = match params.get("client_assertion") {
+        Some(a) => a,
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("client_assertion parameter must be supplied \
+                                             in body"))),
+    };
+
+    // The client is the issuer of its assertion
+    let client_id = match Jwt::decode_unverified(assertion).ok()
+        .and_then(|jwt| jwt.claim_str("iss").map(|iss| ClientId(iss.to_owned())))
+    {
+        Some(cid) => cid,
+        None => return Err(token_error(TokenErrorCode::InvalidClient,
+                                       Some("Malformed client_assertion"))),
+    };
+    if let Some(cid) = params.get("client_id") {
+        if *cid != *client_id {
+            return Err(token_error(TokenErrorCode::InvalidClient,
+                                   Some("client_id does not match client_assertion")));
+        }
+    }
+    let client_data = match server.fetch_client_data(context, &client_id) {
+        Ok(Some(cd)) => cd,
+        _ => return Err(token_error(TokenErrorCode::InvalidClient, Some("No such client"))),
+    };
+
+    let key_set: Box<KeySet> = match client_data.authn_method {
+        ClientAuthnMethod::ClientSecretJwt => Box::new(vec![HmacKey {
+            key: client_data.credentials.as_bytes().to_vec(),
+            key_id: None,
+        }]),
+        ClientAuthnMethod::PrivateKeyJwt => match server.get_client_key_set(context, &client_data)
+        {
+            Ok(Some(k)) => k,
+            _ => return Err(token_error(TokenErrorCode::InvalidClient,
+                                        Some("No keys registered for client"))),
+        },
+        _ => return Err(token_error(TokenErrorCode::InvalidClient,
+                                    Some("Client does not authenticate with \
+                                          client_assertion"))),
+    };
+
+    let audiences = assertion_audiences(server, context);
+    let audiences: Vec<&str> = audiences.iter().map(|a| &**a).collect();
+    let jwt = match verify_client_assertion(assertion, &client_id, &*key_set, &*audiences) {
+        Ok(jwt) => jwt,
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidClient,
+                                         Some("Invalid client_assertion"))),
+    };
+
+    // Each assertion may only be used once
+    let jti = jwt.claim_str("jti").unwrap_or("");
+    let exp = jwt.claim_u64("exp").unwrap_or(0);
+    match server.record_client_assertion_jti(context, &client_id, jti, exp) {
+        Ok(true) => Ok(client_data),
+        Ok(false) => Err(token_error(TokenErrorCode::InvalidClient,
+                                     Some("client_assertion has already been used"))),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidClient, None)),
+    }
+}
+
+/// The audiences which identify this Authorization Server in assertions made for it:
+/// its issuer identifier, and the URLs of its token endpoint and of the other endpoints
+/// which clients call directly (rfc7523 section 3).
+fn assertion_audiences<C, S: AuthzServer<C> + ?Sized>(server: &S, context: &mut C)
+                                                      -> Vec<String>
+{
+    let mut audiences: Vec<String> = Vec::new();
+    if let Some(iss) = server.get_issuer(context) {
+        audiences.push(iss);
+    }
+    if let Some(metadata) = server.get_metadata(context) {
+        let endpoints = vec![metadata.token_endpoint,
+                             metadata.pushed_authorization_request_endpoint,
+                             metadata.device_authorization_endpoint,
+                             metadata.backchannel_authentication_endpoint,
+                             metadata.revocation_endpoint,
+                             metadata.introspection_endpoint];
+        audiences.extend(endpoints.into_iter().filter_map(|e| e));
+    }
+    audiences
+}
+
+/// Authorize a request at the UserInfo endpoint and gather the claims to return
+fn userinfo_claims<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, request: &mut Request)
+    -> Result<BTreeMap<String, Value>, BearerError>
+{
+    let access_token = match try!(extract_bearer_token(request)) {
+        Some(t) => t,
+        None => return Err(BearerError {
+            error: None,
+            error_description: None,
+            error_uri: None,
+            scope: None,
+            unavailable: false,
+        }),
+    };
+
+    let introspection = match server.validate_access_token(context, &*access_token) {
+        Ok(ref i) if i.active && i.sub.is_some() => i.clone(),
+        Ok(_) => return Err(bearer_error(BearerErrorCode::InvalidToken,
+                                         "The access token is not valid")),
+        Err(_) => return Err(bearer_unavailable()),
+    };
+    let scope = introspection.scope.unwrap_or(String::new());
+    if ! scope_contains(&*scope, "openid") {
+        return Err(BearerError {
+            error: Some(BearerErrorCode::InsufficientScope),
+            error_description: Some("The access token was not granted the openid scope"
+                                    .to_owned()),
+            error_uri: None,
+            scope: Some("openid".to_owned()),
+            unavailable: false,
+        });
+    }
+    let sub = introspection.sub.unwrap();
+
+    let claims = match server.get_userinfo_claims(context, &*sub, &*scope) {
+        Ok(c) => c,
+        Err(_) => return Err(bearer_unavailable()),
+    };
+    let mut claims = filter_claims(claims, &*scope);
+    claims.insert("sub".to_owned(), Value::String(sub));
+    Ok(claims)
+}
+
+fn bearer_error(error: BearerErrorCode, description: &str) -> BearerError {
+    BearerError {
+        error: Some(error),
+        error_description: Some(description.to_owned()),
+        error_uri: None,
+        scope: None,
+        unavailable: false,
+    }
+}
+
+/// A failure at the server while validating an access token, reported with no
+/// error code since RFC 6750 has none for it
+fn bearer_unavailable() -> BearerError {
+    BearerError {
+        error: None,
+        error_description: None,
+        error_uri: None,
+        scope: None,
+        unavailable: true,
+    }
+}
+
+/// Respond to a client configuration request whose registration access token is
+/// missing or invalid (RFC 7592 section 3)
+fn configuration_unauthorized(mut response: Response) {
+    let error = bearer_error(BearerErrorCode::InvalidToken,
+                             "The registration access token is not valid");
+    *response.status_mut() = error.status();
+    response.headers_mut().set(WwwAuthenticate(error.as_challenge(None)));
+    let response = response.start().unwrap();
+    let _ = response.end();
+}
+
+/// Respond to a pushed authorization request with an error (RFC 9126 section 2.3)
+fn authz_error_response(mut response: Response, error: AuthzErrorCode, description: &str) {
+    *response.status_mut() = StatusCode::BadRequest;
+    let body = AuthzError {
+        error: error,
+        error_description: Some(description.to_owned()),
+        error_uri: None,
+        state: None,
+    }.as_json();
+    let mut response = response.start().unwrap();
+    response.write_all(body.as_bytes()).unwrap();
+    let _ = response.end();
+}
+
+fn token_error(error: TokenErrorCode, description: Option<&str>) -> TokenError {
+    TokenError {
+        error: error,
+        error_description: description.map(|s| s.to_owned()),
+        error_uri: None,
+    }
+}
+
+/// Check the scope requested at the token endpoint against what the client is allowed,
+/// defaulting to everything the client is allowed if no scope was requested.
+fn requested_scope(client_data: &ClientData, params: &HashMap<String, String>)
+                   -> Result<Option<String>, TokenError>
+{
+    match params.get("scope") {
+        None => Ok(client_data.scope.clone()),
+        Some(s) => {
+            if ! valid_scope_str(s) {
+                return Err(token_error(TokenErrorCode::InvalidScope,
+                                       Some("scope parameter is not valid")));
+            }
+            if let Some(ref allowed) = client_data.scope {
+                if ! scope_is_subset(s, allowed) {
+                    return Err(token_error(TokenErrorCode::InvalidScope,
+                                           Some("scope exceeds the scope allowed for client")));
+                }
+            }
+            Ok(Some(s.clone()))
+        }
+    }
+}
+
+/// Handle the `authorization_code` grant at the token endpoint (rfc6749 section 4.1.3)
+fn authorization_code_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    // Require code, and retrieve the data we issued with the code
+    // This also verifies that the code is valid, and was issued to the
+    // client in question.
+    let code: &String = match params.get("code") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("code parameter must be supplied in body"))),
+        Some(c) => c,
+    };
+    if ! valid_code_str(code) {
+        return Err(token_error(TokenErrorCode::InvalidRequest,
+                               Some("code parameter is not valid")));
+    }
+    let authorization: ClientAuthorization =
+        match server.retrieve_client_authorization(context, code)
+    {
+        Ok(a) => a,
+        _ => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                    Some("Invalid authorization code"))),
+    };
+
+    // Verify the client_id matches
+    if authorization.client_id != client_data.client_id {
+        // FIXME: also delete the stored code and related tokens.
+        return Err(token_error(TokenErrorCode::InvalidGrant, Some("client_id mismatch")));
+    }
+
+    // Verify the redirect_uri matches, if it was used originally
+    if let Some(ru) = params.get("redirect_uri") {
+        if &**ru != &*authorization.redirect_uri.0 {
+            return Err(token_error(TokenErrorCode::InvalidGrant,
+                                   Some("redirect_uri parameter mismatch")));
+        }
+    }
+
+    // Verify the PKCE code verifier, if a code challenge was used originally
+    // (RFC 7636 section 4.6)
+    match (authorization.code_challenge.as_ref(), params.get("code_verifier")) {
+        (None, None) => {},
+        (None, Some(_)) =>
+            return Err(token_error(TokenErrorCode::InvalidGrant,
+                                   Some("code_verifier supplied without a code_challenge"))),
+        (Some(_), None) =>
+            return Err(token_error(TokenErrorCode::InvalidRequest,
+                                   Some("code_verifier parameter must be supplied in body"))),
+        (Some(cc), Some(cv)) => {
+            if ! valid_code_verifier_str(cv) || ! cc.verify(cv) {
+                return Err(token_error(TokenErrorCode::InvalidGrant,
+                                       Some("code_verifier does not match code_challenge")));
+            }
+        }
+    }
+
+    // Build the ID token, if the openid scope was granted (OpenID Connect Core
+    // section 3.1.3.3)
+    let id_token: Option<String> = match authorization.scope {
+        Some(ref scope) if scope_contains(scope, "openid") =>
+            Some(try!(build_id_token(server, context, &authorization))),
+        _ => None,
+    };
+
+    // Issue token
+    let mut token = try!(server.issue_token_to_client(context, code, &client_data.client_id)
+                         .map_err(|_| token_error(TokenErrorCode::InvalidGrant, None)));
+    token.id_token = id_token;
+    Ok(token)
+}
+
+/// Build and sign an ID token for the end-user who authorized `authorization`
+fn build_id_token<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, authorization: &ClientAuthorization)
+    -> Result<String, TokenError>
+{
+    let claims = match server.get_id_token_claims(context, authorization) {
+        Ok(c) => c,
+        Err(OAuthError::AuthzIdTokenUnsupported) =>
+            return Err(token_error(TokenErrorCode::InvalidScope,
+                                   Some("openid scope is not supported"))),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    };
+    if ! claims.satisfies_max_age(authorization.max_age) {
+        return Err(token_error(TokenErrorCode::InvalidGrant,
+                               Some("auth_time is unknown but max_age was requested")));
+    }
+    let issuer = match server.get_issuer(context) {
+        Some(i) => i,
+        None => return Err(token_error(TokenErrorCode::InvalidScope,
+                                       Some("openid scope is not supported"))),
+    };
+    let lifetime = server.get_id_token_lifetime(context);
+    let jwt = claims.to_jwt(&*issuer, &authorization.client_id,
+                            authorization.nonce.as_ref().map(|n| &**n), lifetime);
+    match server.get_signing_key(context) {
+        Some(key) => jwt.sign(key).map_err(|_| token_error(TokenErrorCode::InvalidGrant, None)),
+        None => Err(token_error(TokenErrorCode::InvalidScope,
+                                Some("openid scope is not supported"))),
+    }
+}
+
+/// Handle the `refresh_token` grant at the token endpoint (rfc6749 section 6)
+fn refresh_token_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    // Require refresh_token
+    let refresh_token: &String = match params.get("refresh_token") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("refresh_token parameter must be supplied in body"))),
+        Some(rt) => rt,
+    };
+    if ! valid_refresh_token_str(refresh_token) {
+        return Err(token_error(TokenErrorCode::InvalidRequest,
+                               Some("refresh_token parameter is not valid")));
+    }
+
+    // Retrieve the data we stored with the refresh token.  This also verifies that
+    // the refresh token is valid.
+    let (stored_client_id, stored_scope): (ClientId, Option<String>) =
+        match server.retrieve_refresh_token(context, refresh_token)
+    {
+        Ok(pair) => pair,
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            return Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                         Some("Invalid refresh token"))),
+    };
+
+    // Verify the refresh token was issued to this client
+    if stored_client_id != client_data.client_id {
+        return Err(token_error(TokenErrorCode::InvalidGrant, Some("client_id mismatch")));
+    }
+
+    // rfc6749 section 6: "The requested scope MUST NOT include any scope not
+    // originally granted by the resource owner, and if omitted is treated as
+    // equal to the scope originally granted by the resource owner."
+    let scope: Option<String> = match params.get("scope") {
+        None => stored_scope,
+        Some(s) => {
+            if ! valid_scope_str(s) {
+                return Err(token_error(TokenErrorCode::InvalidScope,
+                                       Some("scope parameter is not valid")));
+            }
+            match stored_scope {
+                Some(ref granted) if scope_is_subset(s, granted) => Some(s.clone()),
+                _ => return Err(token_error(TokenErrorCode::InvalidScope,
+                                            Some("scope exceeds the originally granted scope"))),
+            }
+        }
+    };
+
+    // Issue token
+    match server.rotate_refresh_token(context, refresh_token, &client_data.client_id,
+                                      scope.as_ref().map(|s| &**s))
+    {
+        Ok(t) => Ok(t),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the `client_credentials` grant at the token endpoint (rfc6749 section 4.4)
+fn client_credentials_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    // rfc6749 section 4.4: "The client credentials grant type MUST only be used
+    // by confidential clients."
+    if client_data.client_type != ClientType::ConfidentialClient {
+        return Err(token_error(TokenErrorCode::UnauthorizedClient,
+                               Some("client_credentials grant requires a confidential client")));
+    }
+
+    // Check the requested scope against what the client is allowed
+    let scope: Option<String> = try!(requested_scope(client_data, params));
+
+    // Issue token
+    match server.issue_token_for_client_credentials(context, &client_data.client_id,
+                                                    scope.as_ref().map(|s| &**s))
+    {
+        Ok(t) => Ok(t),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the `password` grant at the token endpoint (rfc6749 section 4.3)
+fn password_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    // This grant type must be explicitly enabled
+    if ! server.allow_password_grant(context, client_data) {
+        return Err(token_error(TokenErrorCode::UnauthorizedClient,
+                               Some("Client is not permitted to use the password grant")));
+    }
+
+    // Require username and password
+    let username: &String = match params.get("username") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("username parameter must be supplied in body"))),
+        Some(u) => u,
+    };
+    let password: &String = match params.get("password") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("password parameter must be supplied in body"))),
+        Some(p) => p,
+    };
+    if ! valid_username_str(username) || ! valid_password_str(password) {
+        return Err(token_error(TokenErrorCode::InvalidRequest,
+                               Some("username or password parameter is not valid")));
+    }
+
+    // Check the requested scope against what the client is allowed
+    let scope: Option<String> = try!(requested_scope(client_data, params));
+
+    // Verify the resource owner credentials
+    match server.verify_resource_owner_credentials(context, username, password) {
+        Ok(true) => {},
+        Ok(false) => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                            Some("Invalid resource owner credentials"))),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            return Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+
+    // Issue token
+    match server.issue_token_for_resource_owner(context, &client_data.client_id, username,
+                                                scope.as_ref().map(|s| &**s))
+    {
+        Ok(t) => Ok(t),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the device code grant at the token endpoint (RFC 8628 section 3.4)
+fn device_code_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    let device_code: &String = match params.get("device_code") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("device_code parameter must be supplied in body"))),
+        Some(dc) => dc,
+    };
+
+    // Report the state of the authorization (RFC 8628 section 3.5)
+    match server.poll_device_authorization(context, &client_data.client_id, device_code) {
+        Ok(DevicePollResult::Granted(token)) => Ok(token),
+        Ok(DevicePollResult::Pending) =>
+            Err(token_error(TokenErrorCode::AuthorizationPending, None)),
+        Ok(DevicePollResult::SlowDown) => Err(token_error(TokenErrorCode::SlowDown, None)),
+        Ok(DevicePollResult::Denied) => Err(token_error(TokenErrorCode::AccessDenied, None)),
+        Ok(DevicePollResult::Expired) => Err(token_error(TokenErrorCode::ExpiredToken, None)),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, Some("Invalid device_code"))),
+    }
+}
+
+/// Handle the JWT bearer grant at the token endpoint (RFC 7523 section 2.1)
+fn jwt_bearer_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    let assertion: &String = match params.get("assertion") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("assertion parameter must be supplied in body"))),
+        Some(a) => a,
+    };
+
+    // Check the requested scope against what the client is allowed
+    let scope: Option<String> = try!(requested_scope(client_data, params));
+
+    // Find the keys trusted for the assertion's issuer
+    let issuer: String = match Jwt::decode_unverified(assertion).ok()
+        .and_then(|jwt| jwt.claim_str("iss").map(|iss| iss.to_owned()))
+    {
+        Some(iss) => iss,
+        None => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                       Some("Malformed assertion"))),
+    };
+    let key_set = match server.get_assertion_key_set(context, &*issuer) {
+        Ok(Some(k)) => k,
+        Ok(None) => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                           Some("Assertion issuer not trusted"))),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            return Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    };
+
+    // rfc7523 section 3: the assertion must be intended for this Authorization Server
+    let audiences = assertion_audiences(server, context);
+    let audiences: Vec<&str> = audiences.iter().map(|a| &**a).collect();
+    let jwt = match verify_jwt_assertion(assertion, &*issuer, &*key_set, &*audiences) {
+        Ok(jwt) => jwt,
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                         Some("Invalid assertion"))),
+    };
+
+    // Issue token
+    match server.issue_token_for_jwt_assertion(context, &client_data.client_id, &jwt,
+                                               scope.as_ref().map(|s| &**s))
+    {
+        Ok(t) => Ok(t),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the SAML 2.0 bearer grant at the token endpoint (RFC 7522 section 2.1)
+fn saml2_bearer_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    let assertion: Vec<u8> = match params.get("assertion") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("assertion parameter must be supplied in body"))),
+        Some(a) => match decode_saml2_assertion(a) {
+            Some(a) => a,
+            None => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                           Some("assertion is not base64url encoded"))),
+        },
+    };
+
+    // Check the requested scope against what the client is allowed
+    let scope: Option<String> = try!(requested_scope(client_data, params));
+
+    // Validate the assertion, mapping it to a subject
+    let subject = match server.validate_saml2_assertion(context, &client_data.client_id,
+                                                        &*assertion) {
+        Ok(s) => s,
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            return Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidGrant,
+                                         Some("Invalid assertion"))),
+    };
+
+    // Issue token
+    match server.issue_token_for_saml2_assertion(context, &client_data.client_id, &*subject,
+                                                 scope.as_ref().map(|s| &**s))
+    {
+        Ok(t) => Ok(t),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the token exchange grant at the token endpoint (RFC 8693 section 2).  This
+/// takes the parameters as pairs, as `audience` and `resource` may be repeated.
+fn token_exchange_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &[(String, String)]) -> Result<TokenData, TokenError>
+{
+    let request = try!(TokenExchangeRequest::from_params(params));
+
+    // Check the requested scope against what the client is allowed
+    if let (Some(scope), Some(allowed)) = (request.scope.as_ref(), client_data.scope.as_ref()) {
+        if ! scope_is_subset(scope, allowed) {
+            return Err(token_error(TokenErrorCode::InvalidScope,
+                                   Some("scope exceeds the scope allowed for client")));
+        }
+    }
+
+    // rfc8693 section 2.2.2: "If either the subject_token or actor_token are invalid for
+    // any reason, or are unacceptable based on policy, the invalid_request error code
+    // MUST be used."
+    let mode = match server.decide_token_exchange(context, &client_data.client_id, &request) {
+        Ok(m) => m,
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            return Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(OAuthError::AuthzTargetInvalid) =>
+            return Err(token_error(TokenErrorCode::InvalidTarget, None)),
+        Err(_) => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                         Some("subject_token or actor_token not accepted"))),
+    };
+
+    // Issue token
+    match server.issue_token_for_exchange(context, &client_data.client_id, &request, mode) {
+        Ok(mut t) => {
+            if t.issued_token_type.is_none() {
+                let issued = request.requested_token_type.unwrap_or(TokenType::AccessToken);
+                t.issued_token_type = Some(format!("{}", issued));
+            }
+            Ok(t)
+        },
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(OAuthError::AuthzTargetInvalid) =>
+            Err(token_error(TokenErrorCode::InvalidTarget, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, None)),
+    }
+}
+
+/// Handle the CIBA grant at the token endpoint (CIBA Core section 10.1)
+fn ciba_grant<C, S: AuthzServer<C> + ?Sized>(
+    server: &mut S, context: &mut C, client_data: &ClientData,
+    params: &HashMap<String, String>) -> Result<TokenData, TokenError>
+{
+    let auth_req_id: &String = match params.get("auth_req_id") {
+        None => return Err(token_error(TokenErrorCode::InvalidRequest,
+                                       Some("auth_req_id parameter must be supplied in body"))),
+        Some(id) => id,
+    };
+
+    // CIBA Core section 4: push mode clients receive their tokens, and must not poll
+    match client_data.backchannel_delivery_mode {
+        Some(DeliveryMode::Poll) | Some(DeliveryMode::Ping) => {},
+        _ => return Err(token_error(TokenErrorCode::UnauthorizedClient,
+                                    Some("Client may not poll for backchannel authentication"))),
+    }
+
+    // Report the state of the request (CIBA Core section 11)
+    match server.poll_backchannel_authentication(context, &client_data.client_id, auth_req_id) {
+        Ok(BackchannelAuthResult::Granted(token)) => Ok(token),
+        Ok(BackchannelAuthResult::Pending) =>
+            Err(token_error(TokenErrorCode::AuthorizationPending, None)),
+        Ok(BackchannelAuthResult::SlowDown) => Err(token_error(TokenErrorCode::SlowDown, None)),
+        Ok(BackchannelAuthResult::Denied) =>
+            Err(token_error(TokenErrorCode::AccessDenied, None)),
+        Ok(BackchannelAuthResult::Expired) =>
+            Err(token_error(TokenErrorCode::ExpiredToken, None)),
+        Err(OAuthError::AuthzGrantTypeUnsupported) =>
+            Err(token_error(TokenErrorCode::UnsupportedGrantType, None)),
+        Err(_) => Err(token_error(TokenErrorCode::InvalidGrant, Some("Invalid auth_req_id"))),
+    }
+}
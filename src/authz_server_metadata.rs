@@ -0,0 +1,326 @@
+
+use std::io::Read;
+use hyper::status::StatusCode;
+use url::Url;
+use jwt::JwsKey;
+use json::{push_json_string, push_json_strings, push_json_bool};
+use OAuthError;
+
+/// Authorization Server Metadata, published at a well-known URL so that clients can
+/// discover the Authorization Server's endpoints and capabilities (RFC 8414).
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuthzServerMetadata {
+    /// The Authorization Server's issuer identifier, an https URL with no query or
+    /// fragment.  Required.
+    pub issuer: String,
+    /// URL of the authorization endpoint
+    pub authorization_endpoint: Option<String>,
+    /// URL of the token endpoint
+    pub token_endpoint: Option<String>,
+    /// URL of the JSON Web Key Set document
+    pub jwks_uri: Option<String>,
+    /// URL of the dynamic client registration endpoint
+    pub registration_endpoint: Option<String>,
+    /// Scope values supported
+    pub scopes_supported: Option<Vec<String>>,
+    /// response_type values supported.  Required.
+    pub response_types_supported: Vec<String>,
+    /// response_mode values supported
+    pub response_modes_supported: Option<Vec<String>>,
+    /// grant_type values supported.  If omitted, the default is
+    /// ["authorization_code", "implicit"].
+    pub grant_types_supported: Option<Vec<String>>,
+    /// Client authentication methods supported at the token endpoint.  If omitted,
+    /// the default is "client_secret_basic".
+    pub token_endpoint_auth_methods_supported: Option<Vec<String>>,
+    /// JWS algorithms supported for signing client assertions, for the
+    /// "client_secret_jwt" and "private_key_jwt" authentication methods
+    pub token_endpoint_auth_signing_alg_values_supported: Option<Vec<String>>,
+    /// URL of human-readable documentation for developers
+    pub service_documentation: Option<String>,
+    /// URL of the revocation endpoint (RFC 7009)
+    pub revocation_endpoint: Option<String>,
+    /// URL of the introspection endpoint (RFC 7662)
+    pub introspection_endpoint: Option<String>,
+    /// PKCE code challenge methods supported (RFC 7636)
+    pub code_challenge_methods_supported: Option<Vec<String>>,
+    /// URL of the device authorization endpoint (RFC 8628)
+    pub device_authorization_endpoint: Option<String>,
+    /// URL of the pushed authorization request endpoint (RFC 9126)
+    pub pushed_authorization_request_endpoint: Option<String>,
+    /// Whether all clients must use pushed authorization requests (RFC 9126)
+    pub require_pushed_authorization_requests: Option<bool>,
+    /// Whether the `request` parameter is supported (RFC 9101)
+    pub request_parameter_supported: Option<bool>,
+    /// Whether the `request_uri` parameter is supported for request objects (RFC 9101)
+    pub request_uri_parameter_supported: Option<bool>,
+    /// Whether all clients must sign their authorization requests (RFC 9101)
+    pub require_signed_request_object: Option<bool>,
+    /// JWS algorithms supported for signing request objects (RFC 9101)
+    pub request_object_signing_alg_values_supported: Option<Vec<String>>,
+    /// URL of the backchannel authentication endpoint (CIBA)
+    pub backchannel_authentication_endpoint: Option<String>,
+    /// Backchannel token delivery modes supported: "poll", "ping" and/or "push" (CIBA)
+    pub backchannel_token_delivery_modes_supported: Option<Vec<String>>,
+}
+
+impl AuthzServerMetadata {
+    /// Create metadata for an Authorization Server with the given issuer identifier and
+    /// endpoints, advertising what this library supports by default.  Adjust the fields
+    /// afterwards to advertise anything else you support (e.g. further grant types), or
+    /// to withdraw what you do not: "private_key_jwt" and "RS256" client authentication
+    /// need `AuthzServer::get_client_key_set()`, and "none" is for public clients.
+    pub fn new(issuer: &str, authorization_endpoint: &str, token_endpoint: &str)
+               -> AuthzServerMetadata
+    {
+        AuthzServerMetadata {
+            issuer: issuer.to_owned(),
+            authorization_endpoint: Some(authorization_endpoint.to_owned()),
+            token_endpoint: Some(token_endpoint.to_owned()),
+            jwks_uri: None,
+            registration_endpoint: None,
+            scopes_supported: None,
+            response_types_supported: vec!["code".to_owned()],
+            response_modes_supported: None,
+            grant_types_supported: Some(vec!["authorization_code".to_owned()]),
+            token_endpoint_auth_methods_supported: Some(vec!["client_secret_basic".to_owned(),
+                                                             "client_secret_post".to_owned(),
+                                                             "client_secret_jwt".to_owned(),
+                                                             "private_key_jwt".to_owned(),
+                                                             "none".to_owned()]),
+            token_endpoint_auth_signing_alg_values_supported: Some(vec!["HS256".to_owned(),
+                                                                        "RS256".to_owned()]),
+            service_documentation: None,
+            revocation_endpoint: None,
+            introspection_endpoint: None,
+            code_challenge_methods_supported: Some(vec!["S256".to_owned(), "plain".to_owned()]),
+            device_authorization_endpoint: None,
+            pushed_authorization_request_endpoint: None,
+            require_pushed_authorization_requests: None,
+            request_parameter_supported: None,
+            request_uri_parameter_supported: None,
+            require_signed_request_object: None,
+            request_object_signing_alg_values_supported: None,
+            backchannel_authentication_endpoint: None,
+            backchannel_token_delivery_modes_supported: None,
+        }
+    }
+
+    /// Serialize as a JSON object, leaving out the fields which are None
+    pub fn as_json(&self) -> String {
+        let mut json_str = String::new();
+        self.push_json(&mut json_str);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+
+    /// Start a JSON object under construction with the metadata fields which are Some
+    fn push_json(&self, json_str: &mut String) {
+        json_str.push_str( &*format!("{{\r\n  \"issuer\": {}",
+                                     ::serde_json::to_string(&self.issuer).unwrap()) );
+        push_json_string(json_str, "authorization_endpoint", &self.authorization_endpoint);
+        push_json_string(json_str, "token_endpoint", &self.token_endpoint);
+        push_json_string(json_str, "jwks_uri", &self.jwks_uri);
+        push_json_string(json_str, "registration_endpoint", &self.registration_endpoint);
+        push_json_strings(json_str, "scopes_supported", &self.scopes_supported);
+        json_str.push_str( &*format!(",\r\n  \"response_types_supported\": {}",
+                                     ::serde_json::to_string(
+                                         &self.response_types_supported).unwrap()) );
+        push_json_strings(json_str, "response_modes_supported",
+                          &self.response_modes_supported);
+        push_json_strings(json_str, "grant_types_supported", &self.grant_types_supported);
+        push_json_strings(json_str, "token_endpoint_auth_methods_supported",
+                          &self.token_endpoint_auth_methods_supported);
+        push_json_strings(json_str, "token_endpoint_auth_signing_alg_values_supported",
+                          &self.token_endpoint_auth_signing_alg_values_supported);
+        push_json_string(json_str, "service_documentation", &self.service_documentation);
+        push_json_string(json_str, "revocation_endpoint", &self.revocation_endpoint);
+        push_json_string(json_str, "introspection_endpoint", &self.introspection_endpoint);
+        push_json_strings(json_str, "code_challenge_methods_supported",
+                          &self.code_challenge_methods_supported);
+        push_json_string(json_str, "device_authorization_endpoint",
+                         &self.device_authorization_endpoint);
+        push_json_string(json_str, "pushed_authorization_request_endpoint",
+                         &self.pushed_authorization_request_endpoint);
+        push_json_bool(json_str, "require_pushed_authorization_requests",
+                       &self.require_pushed_authorization_requests);
+        push_json_bool(json_str, "request_parameter_supported",
+                       &self.request_parameter_supported);
+        push_json_bool(json_str, "request_uri_parameter_supported",
+                       &self.request_uri_parameter_supported);
+        push_json_bool(json_str, "require_signed_request_object",
+                       &self.require_signed_request_object);
+        push_json_strings(json_str, "request_object_signing_alg_values_supported",
+                          &self.request_object_signing_alg_values_supported);
+        push_json_string(json_str, "backchannel_authentication_endpoint",
+                         &self.backchannel_authentication_endpoint);
+        push_json_strings(json_str, "backchannel_token_delivery_modes_supported",
+                          &self.backchannel_token_delivery_modes_supported);
+    }
+
+    /// The URL at which the metadata for the given issuer is published (RFC 8414
+    /// section 3), which inserts the well-known path between the host and any path
+    /// component of the issuer.
+    pub fn well_known_url(issuer: &Url) -> Url {
+        let mut url = issuer.clone();
+        let path = issuer.path().trim_right_matches('/').to_owned();
+        url.set_path(&*format!("/.well-known/oauth-authorization-server{}", path));
+        url.set_query(None);
+        url.set_fragment(None);
+        url
+    }
+
+    /// Fetch and parse the metadata of the Authorization Server with the given issuer
+    /// identifier, so that its endpoint URLs need not be hardcoded into the client.
+    ///
+    /// The `issuer` in the fetched metadata is verified to be identical to `issuer` (RFC
+    /// 8414 section 3.3).
+    pub fn fetch(issuer: &Url) -> Result<AuthzServerMetadata, OAuthError> {
+        let hyper = ::hyper::client::Client::new();
+        let mut res = try!(hyper.get(AuthzServerMetadata::well_known_url(issuer)).send());
+
+        let mut body: Vec<u8> = Vec::new();
+        try!(res.read_to_end(&mut body));
+        let bodystr = try!(String::from_utf8(body));
+
+        if res.status != StatusCode::Ok {
+            return Err(OAuthError::UnexpectedStatusCode);
+        }
+
+        let metadata: AuthzServerMetadata = try!(::serde_json::from_str(&bodystr));
+        // Compare as parsed URLs, since a `Url` always has a path (so "https://as.example"
+        // is held as "https://as.example/"); any other difference is a mismatch.
+        match Url::parse(&*metadata.issuer) {
+            Ok(ref u) if u == issuer => {},
+            _ => return Err(OAuthError::ClientIssuerMismatch),
+        }
+        Ok(metadata)
+    }
+
+    /// The authorization endpoint, for passing to `Client::start_oauth()`
+    pub fn authorization_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.authorization_endpoint)
+    }
+
+    /// The token endpoint, for passing to `Client::handle_redirect_url()` and the
+    /// other `Client` methods which request tokens.
+    pub fn token_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.token_endpoint)
+    }
+
+    /// The device authorization endpoint, for passing to
+    /// `Client::request_device_authorization()`
+    pub fn device_authorization_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.device_authorization_endpoint)
+    }
+
+    /// The pushed authorization request endpoint, for passing to
+    /// `Client::push_authz_request()`
+    pub fn pushed_authorization_request_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.pushed_authorization_request_endpoint)
+    }
+
+    /// The backchannel authentication endpoint, for passing to
+    /// `Client::request_backchannel_authentication()`
+    pub fn backchannel_authentication_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.backchannel_authentication_endpoint)
+    }
+
+    /// The revocation endpoint, for passing to `Client::revoke_token()`
+    pub fn revocation_endpoint_url(&self) -> Result<Url, OAuthError> {
+        endpoint_url(&self.revocation_endpoint)
+    }
+}
+
+/// OpenID Provider Metadata, published at a well-known URL so that relying parties can
+/// discover the OpenID Provider's endpoints, capabilities and signing keys (OpenID
+/// Connect Discovery section 3).  This extends the Authorization Server Metadata.
+#[derive(Clone, Debug)]
+pub struct ProviderMetadata {
+    /// The Authorization Server Metadata.  `jwks_uri` is required.
+    pub oauth: AuthzServerMetadata,
+    /// URL of the UserInfo endpoint
+    pub userinfo_endpoint: Option<String>,
+    /// Subject identifier types supported, "public" and/or "pairwise".  Required.
+    pub subject_types_supported: Vec<String>,
+    /// JWS algorithms used to sign ID tokens.  Required.  OpenID Connect Discovery
+    /// expects "RS256" among them, so that is the algorithm to sign with by default.
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    /// Claims that the OpenID Provider may be able to supply values for
+    pub claims_supported: Option<Vec<String>>,
+}
+
+impl ProviderMetadata {
+    /// Create metadata for an OpenID Provider with the given issuer identifier,
+    /// endpoints and JSON Web Key Set URL, advertising what this library supports by
+    /// default, and the algorithm of `signing_key` (as returned by
+    /// `AuthzServer::get_signing_key()`) for ID tokens.  Adjust the fields afterwards
+    /// to advertise anything else you support.
+    pub fn new(issuer: &str, authorization_endpoint: &str, token_endpoint: &str,
+               jwks_uri: &str, signing_key: &JwsKey) -> ProviderMetadata
+    {
+        let mut oauth = AuthzServerMetadata::new(issuer, authorization_endpoint,
+                                                 token_endpoint);
+        oauth.jwks_uri = Some(jwks_uri.to_owned());
+        oauth.scopes_supported = Some(vec!["openid".to_owned()]);
+        ProviderMetadata {
+            oauth: oauth,
+            userinfo_endpoint: None,
+            subject_types_supported: vec!["public".to_owned()],
+            id_token_signing_alg_values_supported: vec![signing_key.algorithm().to_owned()],
+            claims_supported: None,
+        }
+    }
+
+    /// Serialize as a JSON object, leaving out the fields which are None
+    pub fn as_json(&self) -> String {
+        let mut json_str = String::new();
+        self.oauth.push_json(&mut json_str);
+        push_json_string(&mut json_str, "userinfo_endpoint", &self.userinfo_endpoint);
+        json_str.push_str( &*format!(",\r\n  \"subject_types_supported\": {}",
+                                     ::serde_json::to_string(
+                                         &self.subject_types_supported).unwrap()) );
+        json_str.push_str( &*format!(",\r\n  \"id_token_signing_alg_values_supported\": {}",
+                                     ::serde_json::to_string(
+                                         &self.id_token_signing_alg_values_supported)
+                                     .unwrap()) );
+        push_json_strings(&mut json_str, "claims_supported", &self.claims_supported);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+
+    /// The URL at which the metadata for the given issuer is published (OpenID Connect
+    /// Discovery section 4), which appends the well-known path to the issuer.
+    pub fn well_known_url(issuer: &Url) -> Url {
+        let mut url = issuer.clone();
+        let path = issuer.path().trim_right_matches('/').to_owned();
+        url.set_path(&*format!("{}/.well-known/openid-configuration", path));
+        url.set_query(None);
+        url.set_fragment(None);
+        url
+    }
+}
+
+fn endpoint_url(endpoint: &Option<String>) -> Result<Url, OAuthError> {
+    match *endpoint {
+        None => Err(OAuthError::ClientEndpointMissing),
+        Some(ref e) => Ok(try!(Url::parse(e))),
+    }
+}
+
+#[test]
+fn test_well_known_url() {
+    let issuer = Url::parse("https://example.com").unwrap();
+    assert_eq!(AuthzServerMetadata::well_known_url(&issuer).as_str(),
+               "https://example.com/.well-known/oauth-authorization-server");
+    let issuer = Url::parse("https://example.com/issuer1").unwrap();
+    assert_eq!(AuthzServerMetadata::well_known_url(&issuer).as_str(),
+               "https://example.com/.well-known/oauth-authorization-server/issuer1");
+}
+
+#[test]
+fn test_provider_metadata_well_known_url() {
+    let issuer = Url::parse("https://example.com/issuer1").unwrap();
+    assert_eq!(ProviderMetadata::well_known_url(&issuer).as_str(),
+               "https://example.com/issuer1/.well-known/openid-configuration");
+}
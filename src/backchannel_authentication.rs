@@ -0,0 +1,321 @@
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::io::Read;
+use hyper::header::{Authorization, Bearer, ContentType};
+use hyper::status::StatusCode;
+use url::form_urlencoded::Serializer;
+use serde_json::Value;
+use json::push_json_number;
+use scope::scope_contains;
+use syntax::valid_scope_str;
+use {ClientData, OAuthError, TokenData, TokenError, TokenErrorCode};
+
+/// The grant_type value for exchanging an `auth_req_id` at the token endpoint
+pub const CIBA_GRANT_TYPE: &'static str = "urn:openid:params:grant-type:ciba";
+
+/// Lifetime of backchannel authentication requests, in seconds, unless the client
+/// requests a shorter one
+pub const BACKCHANNEL_AUTH_LIFETIME: u64 = 600;
+
+/// Minimum time between polls of the token endpoint by the client, in seconds
+pub const BACKCHANNEL_POLL_INTERVAL: u64 = 5;
+
+/// How the result of a backchannel authentication is delivered to the client
+/// (CIBA Core section 5).  Registered for each client.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeliveryMode {
+    /// The client polls the token endpoint
+    Poll,
+    /// The client is notified at its notification endpoint, and then calls the token
+    /// endpoint
+    Ping,
+    /// The tokens are sent to the client's notification endpoint
+    Push,
+}
+
+impl DeliveryMode {
+    /// Parse a `backchannel_token_delivery_mode`, returning None for unknown modes
+    pub fn from_str(mode: &str) -> Option<DeliveryMode> {
+        match mode {
+            "poll" => Some(DeliveryMode::Poll),
+            "ping" => Some(DeliveryMode::Ping),
+            "push" => Some(DeliveryMode::Push),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DeliveryMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            DeliveryMode::Poll => write!(f, "poll"),
+            DeliveryMode::Ping => write!(f, "ping"),
+            DeliveryMode::Push => write!(f, "push"),
+        }
+    }
+}
+
+/// A backchannel authentication request (CIBA Core section 7.1), made by a client
+/// (such as a call-center application) to have a user authenticate and approve the
+/// request on their own device.
+#[derive(Clone, Debug)]
+pub struct BackchannelAuthRequest {
+    /// Space-delimited scope, which must include `openid`
+    pub scope: String,
+    /// Identifies the user, e.g. by email address or phone number.  Exactly one of
+    /// `login_hint`, `login_hint_token` and `id_token_hint` is required.
+    pub login_hint: Option<String>,
+    pub login_hint_token: Option<String>,
+    pub id_token_hint: Option<String>,
+    /// A short message shown both on the client and on the user's device, so the user
+    /// can tell that the request they approve is the one they started
+    pub binding_message: Option<String>,
+    /// Requested authentication context class references
+    pub acr_values: Option<String>,
+    /// Requested lifetime of the request, in seconds
+    pub requested_expiry: Option<u64>,
+    /// Bearer token for notifying the client, required in ping and push modes
+    pub client_notification_token: Option<String>,
+}
+
+impl BackchannelAuthRequest {
+    /// Create a request for `scope` (to which `openid` is added if missing) for the user
+    /// identified by `login_hint`, leaving the other parameters to be filled in.
+    pub fn new(scope: &str, login_hint: &str) -> BackchannelAuthRequest {
+        BackchannelAuthRequest {
+            scope: if scope_contains(scope, "openid") {
+                scope.to_owned()
+            } else {
+                format!("openid {}", scope).trim().to_owned()
+            },
+            login_hint: Some(login_hint.to_owned()),
+            login_hint_token: None,
+            id_token_hint: None,
+            binding_message: None,
+            acr_values: None,
+            requested_expiry: None,
+            client_notification_token: None,
+        }
+    }
+
+    /// Read the request from the backchannel authentication endpoint parameters,
+    /// checking that they are valid for a client using `mode` (CIBA Core section 7.1).
+    pub fn from_params(params: &HashMap<String, String>, mode: DeliveryMode)
+                       -> Result<BackchannelAuthRequest, TokenError>
+    {
+        let scope = match params.get("scope") {
+            Some(s) if valid_scope_str(s) && scope_contains(s, "openid") => s.clone(),
+            _ => return Err(error(TokenErrorCode::InvalidScope,
+                                  "scope must be supplied and include openid")),
+        };
+
+        let login_hint = params.get("login_hint").cloned();
+        let login_hint_token = params.get("login_hint_token").cloned();
+        let id_token_hint = params.get("id_token_hint").cloned();
+        let hints = [&login_hint, &login_hint_token, &id_token_hint].iter()
+            .filter(|h| h.is_some()).count();
+        if hints != 1 {
+            return Err(error(TokenErrorCode::InvalidRequest,
+                             "Exactly one of login_hint, login_hint_token and id_token_hint \
+                              must be supplied"));
+        }
+
+        let requested_expiry = match params.get("requested_expiry") {
+            None => None,
+            Some(e) => match e.parse::<u64>() {
+                Ok(e) if e > 0 => Some(e),
+                _ => return Err(error(TokenErrorCode::InvalidRequest,
+                                      "Invalid requested_expiry")),
+            },
+        };
+
+        let client_notification_token = params.get("client_notification_token").cloned();
+        if mode != DeliveryMode::Poll && client_notification_token.is_none() {
+            return Err(error(TokenErrorCode::InvalidRequest,
+                             "client_notification_token must be supplied"));
+        }
+
+        Ok(BackchannelAuthRequest {
+            scope: scope,
+            login_hint: login_hint,
+            login_hint_token: login_hint_token,
+            id_token_hint: id_token_hint,
+            binding_message: params.get("binding_message").cloned(),
+            acr_values: params.get("acr_values").cloned(),
+            requested_expiry: requested_expiry,
+            client_notification_token: client_notification_token,
+        })
+    }
+
+    /// The url-encoded body of the request to the backchannel authentication endpoint
+    pub fn to_form(&self) -> String {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("scope", &*self.scope);
+        let optional = [("login_hint", &self.login_hint),
+                        ("login_hint_token", &self.login_hint_token),
+                        ("id_token_hint", &self.id_token_hint),
+                        ("binding_message", &self.binding_message),
+                        ("acr_values", &self.acr_values),
+                        ("client_notification_token", &self.client_notification_token)];
+        for &(name, value) in &optional {
+            if let Some(ref v) = *value {
+                serializer.append_pair(name, v);
+            }
+        }
+        if let Some(e) = self.requested_expiry {
+            serializer.append_pair("requested_expiry", &*format!("{}", e));
+        }
+        serializer.finish()
+    }
+}
+
+/// The state of a backchannel authentication request, as reported by
+/// `AuthzServer::poll_backchannel_authentication()` when the client polls the token
+/// endpoint (CIBA Core section 11), or delivered to a push mode client.
+#[derive(Debug)]
+pub enum BackchannelAuthResult {
+    /// The user has not yet approved or denied the request
+    Pending,
+    /// The client is polling too often, and must wait 5 seconds longer between polls
+    SlowDown,
+    /// The user denied the request
+    Denied,
+    /// The request has expired
+    Expired,
+    /// The user approved the request, and these tokens were issued.  For push mode
+    /// clients, the ID token must include the `urn:openid:params:jwt:claim:auth_req_id`
+    /// claim, along with `at_hash` (and `rt_hash` if there is a refresh token), so that
+    /// the client can tie the pushed tokens to its request (CIBA Core section 10.3.1).
+    Granted(TokenData),
+}
+
+/// The response from the backchannel authentication endpoint (CIBA Core section 7.3).
+/// The client then waits for the result with `auth_req_id`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackchannelAuthResponse {
+    pub auth_req_id: String,
+    pub expires_in: u64,
+    /// Minimum time between polls of the token endpoint, in seconds.  Not supplied in
+    /// push mode.
+    pub interval: Option<u64>,
+}
+
+impl BackchannelAuthResponse {
+    /// Serialize as the JSON backchannel authentication response, leaving out
+    /// `interval` if it is None.  Serde deserializes a missing `interval` as None, so it
+    /// can parse the result.
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"auth_req_id\": {},\r\n  \"expires_in\": {}",
+                                   ::serde_json::to_string(&self.auth_req_id).unwrap(),
+                                   self.expires_in);
+        push_json_number(&mut json_str, "interval", &self.interval);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
+
+/// Notify a client using ping or push mode that the backchannel authentication
+/// `auth_req_id` is complete (CIBA Core section 10), by POSTing to the client's
+/// `backchannel_notification_endpoint` with the `client_notification_token` it sent
+/// with the request.  In ping mode the client then calls the token endpoint.  In push
+/// mode the result is sent along: the tokens if granted, or else the error.  Push mode
+/// tokens must include an ID token with the `urn:openid:params:jwt:claim:auth_req_id`
+/// claim (CIBA Core section 10.3.1).
+///
+/// Call this once the user has approved or denied the request, or it has expired.
+/// Do nothing for poll mode clients.
+pub fn send_backchannel_notification(client_data: &ClientData,
+                                     client_notification_token: &str, auth_req_id: &str,
+                                     result: &BackchannelAuthResult)
+                                     -> Result<(), OAuthError>
+{
+    let endpoint = match client_data.backchannel_notification_endpoint {
+        Some(ref e) => e,
+        None => return Err(OAuthError::ClientEndpointMissing),
+    };
+    let body = match (client_data.backchannel_delivery_mode, result) {
+        (Some(DeliveryMode::Ping), _) => try!(notification_json(auth_req_id, "{}")),
+        (Some(DeliveryMode::Push), &BackchannelAuthResult::Granted(ref token)) =>
+            try!(notification_json(auth_req_id, &*token.as_json())),
+        (Some(DeliveryMode::Push), &BackchannelAuthResult::Denied) =>
+            try!(notification_json(auth_req_id, &*error(
+                TokenErrorCode::AccessDenied, "The user denied the request").as_json())),
+        (Some(DeliveryMode::Push), &BackchannelAuthResult::Expired) =>
+            try!(notification_json(auth_req_id, &*error(
+                TokenErrorCode::ExpiredToken, "The request expired").as_json())),
+        _ => return Ok(()),
+    };
+
+    let hyper = ::hyper::client::Client::new();
+    let mut res = try!(hyper.post(&**endpoint)
+                       .header(ContentType::json())
+                       .header(Authorization(Bearer {
+                           token: client_notification_token.to_owned()
+                       }))
+                       .body(&*body)
+                       .send());
+    let mut response_body: Vec<u8> = Vec::new();
+    try!(res.read_to_end(&mut response_body));
+
+    match res.status {
+        StatusCode::Ok | StatusCode::NoContent => Ok(()),
+        _ => Err(OAuthError::UnexpectedStatusCode),
+    }
+}
+
+/// The body of a notification: the JSON object `json` with `auth_req_id` added
+fn notification_json(auth_req_id: &str, json: &str) -> Result<String, OAuthError> {
+    let mut value: Value = try!(::serde_json::from_str(json));
+    match value {
+        Value::Object(ref mut object) => {
+            object.insert("auth_req_id".to_owned(), Value::String(auth_req_id.to_owned()));
+        },
+        _ => return Err(OAuthError::AuthzBadRequest),
+    }
+    Ok(try!(::serde_json::to_string(&value)))
+}
+
+fn error(error: TokenErrorCode, description: &str) -> TokenError {
+    TokenError {
+        error: error,
+        error_description: Some(description.to_owned()),
+        error_uri: None,
+    }
+}
+
+#[test]
+fn test_backchannel_auth_request_params() {
+    let mut request = BackchannelAuthRequest::new("email", "+1 (425) 555-1212");
+    assert_eq!(&*request.scope, "openid email");
+    request.binding_message = Some("W4SCT".to_owned());
+    let form = request.to_form();
+
+    let mut params: HashMap<String, String> = ::url::form_urlencoded::parse(form.as_bytes())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let parsed = BackchannelAuthRequest::from_params(&params, DeliveryMode::Poll).unwrap();
+    assert_eq!(parsed.login_hint, request.login_hint);
+    assert_eq!(parsed.binding_message, request.binding_message);
+
+    // Ping and push modes need a token to notify the client with
+    assert!(BackchannelAuthRequest::from_params(&params, DeliveryMode::Ping).is_err());
+
+    params.insert("id_token_hint".to_owned(), "eyJ...".to_owned());
+    assert!(BackchannelAuthRequest::from_params(&params, DeliveryMode::Poll).is_err());
+}
+
+#[test]
+fn test_notification_json() {
+    let json = error(TokenErrorCode::AccessDenied, "The user denied the request").as_json();
+    let body = notification_json("1c266114-a1be-4252-8ad1-04986c5b9ac1", &*json).unwrap();
+    let value: Value = ::serde_json::from_str(&*body).unwrap();
+    assert_eq!(value.find("auth_req_id").and_then(|v| v.as_string()),
+               Some("1c266114-a1be-4252-8ad1-04986c5b9ac1"));
+    assert_eq!(value.find("error").and_then(|v| v.as_string()), Some("access_denied"));
+
+    let body = notification_json("1c266114-a1be-4252-8ad1-04986c5b9ac1", "{}").unwrap();
+    assert_eq!(body, "{\"auth_req_id\":\"1c266114-a1be-4252-8ad1-04986c5b9ac1\"}");
+}
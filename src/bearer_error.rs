@@ -0,0 +1,118 @@
+
+use hyper::status::StatusCode;
+
+/// Error codes for a request to a protected resource using a bearer token
+/// (RFC 6750 section 3.1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BearerErrorCode {
+    /// The request is missing a required parameter, includes an unsupported parameter
+    /// or parameter value, repeats the same parameter, uses more than one method for
+    /// including an access token, or is otherwise malformed.
+    InvalidRequest,
+    /// The access token provided is expired, revoked, malformed, or invalid for other
+    /// reasons.
+    InvalidToken,
+    /// The request requires higher privileges than provided by the access token.
+    InsufficientScope,
+}
+
+impl From<BearerErrorCode> for &'static str {
+    fn from(e: BearerErrorCode) -> &'static str {
+        match e {
+            BearerErrorCode::InvalidRequest => "invalid_request",
+            BearerErrorCode::InvalidToken => "invalid_token",
+            BearerErrorCode::InsufficientScope => "insufficient_scope",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BearerError {
+    /// The error code, or None if the request simply lacked any authentication
+    /// information (RFC 6750 section 3.1)
+    pub error: Option<BearerErrorCode>,
+    pub error_description: Option<String>,
+    pub error_uri: Option<String>,
+    /// The scope required to access the resource, for `InsufficientScope` errors
+    pub scope: Option<String>,
+    /// True if the access token could not be validated because of a failure at the
+    /// server.  This is not an RFC 6750 error, so it is reported with no error code
+    /// and a 503 status, and the client should retry later rather than discard the token.
+    pub unavailable: bool,
+}
+
+impl BearerError {
+    /// The HTTP status code to respond with (RFC 6750 section 3.1)
+    pub fn status(&self) -> StatusCode {
+        if self.unavailable {
+            return StatusCode::ServiceUnavailable;
+        }
+        match self.error {
+            None => StatusCode::Unauthorized,
+            Some(BearerErrorCode::InvalidRequest) => StatusCode::BadRequest,
+            Some(BearerErrorCode::InvalidToken) => StatusCode::Unauthorized,
+            Some(BearerErrorCode::InsufficientScope) => StatusCode::Forbidden,
+        }
+    }
+
+    // BearerErrors are returned via the WWW-Authenticate response header.
+    // This function generates the value of that header.
+    pub fn as_challenge(&self, realm: Option<&str>) -> String {
+        let mut params: Vec<String> = Vec::new();
+        if let Some(r) = realm {
+            params.push(format!("realm=\"{}\"", r));
+        }
+        if let Some(e) = self.error {
+            params.push(format!("error=\"{}\"", <&'static str as From<BearerErrorCode>>::from(e)));
+        }
+        if self.error_description.is_some() {
+            params.push(format!("error_description=\"{}\"",
+                                self.error_description.as_ref().unwrap()));
+        }
+        if self.error_uri.is_some() {
+            params.push(format!("error_uri=\"{}\"", self.error_uri.as_ref().unwrap()));
+        }
+        if self.scope.is_some() {
+            params.push(format!("scope=\"{}\"", self.scope.as_ref().unwrap()));
+        }
+        if params.is_empty() {
+            "Bearer".to_owned()
+        } else {
+            format!("Bearer {}", params.join(", "))
+        }
+    }
+}
+
+#[test]
+fn test_bearer_error_as_challenge() {
+    let error = BearerError {
+        error: Some(BearerErrorCode::InvalidToken),
+        error_description: Some("The access token expired".to_owned()),
+        error_uri: None,
+        scope: None,
+        unavailable: false,
+    };
+    assert_eq!(&*error.as_challenge(Some("example")),
+               "Bearer realm=\"example\", error=\"invalid_token\", \
+                error_description=\"The access token expired\"");
+    assert_eq!(error.status(), StatusCode::Unauthorized);
+
+    let error = BearerError {
+        error: None,
+        error_description: None,
+        error_uri: None,
+        scope: None,
+        unavailable: false,
+    };
+    assert_eq!(&*error.as_challenge(None), "Bearer");
+
+    let error = BearerError {
+        error: None,
+        error_description: None,
+        error_uri: None,
+        scope: None,
+        unavailable: true,
+    };
+    assert_eq!(&*error.as_challenge(None), "Bearer");
+    assert_eq!(error.status(), StatusCode::ServiceUnavailable);
+}
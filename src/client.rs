@@ -1,13 +1,24 @@
 
 use std::io::Read;
+use std::time::Duration;
 use hyper::server::{Request, Response};
 use hyper::uri::RequestUri;
 use hyper::status::StatusCode;
-use hyper::header::{Location, Authorization, Basic};
+use hyper::header::{Location, ContentType};
 use url::Url;
 use url::percent_encoding::{QUERY_ENCODE_SET, percent_encode};
+use url::form_urlencoded::Serializer;
+use serde_json::Value;
 use textnonce::TextNonce;
-use {ClientData, OAuthError, TokenData, AuthzError};
+use {ClientData, ClientAuthnMethod, OAuthError, TokenData, TokenError, TokenErrorCode,
+     AuthzError, CodeChallenge, CodeChallengeMethod, TokenTypeHint, DeviceAuthorization,
+     TokenExchangeRequest, BackchannelAuthRequest, BackchannelAuthResponse, DeliveryMode,
+     JwsKey, HmacKey};
+use device_authorization::DEVICE_CODE_GRANT_TYPE;
+use jwt_bearer::JWT_BEARER_GRANT_TYPE;
+use saml2_bearer::{encode_saml2_assertion, SAML2_BEARER_GRANT_TYPE};
+use backchannel_authentication::CIBA_GRANT_TYPE;
+use client_authn_method::{sign_client_assertion, CLIENT_ASSERTION_TYPE};
 
 pub trait Client
 {
@@ -27,11 +38,67 @@ pub trait Client
         token
     }
 
+    /// Store a PKCE code verifier (RFC 7636), keyed by the state nonce of the
+    /// authorization request it was generated for.
+    fn store_code_verifier(&mut self, state: &str, code_verifier: &str);
+
+    /// Consume the PKCE code verifier stored for the given state nonce from storage,
+    /// returning None if there is none.
+    fn consume_code_verifier(&mut self, state: &str) -> Option<String>;
+
+    fn generate_code_verifier(&mut self, state: &str) -> String {
+        // 64 characters of the base64url alphabet are valid per RFC 7636 section 4.1
+        let code_verifier = TextNonce::sized_urlsafe(64).unwrap().into_string();
+        self.store_code_verifier(state, &*code_verifier);
+        code_verifier
+    }
+
     /// Get the redirect URI for this client
     fn get_redirect_uri<'a>(&'a self) -> &'a str;
 
+    /// Get the method this client authenticates to the AuthzServer with, which must be
+    /// the one registered for it.
+    ///
+    /// The default implementation takes it from the client data.
+    fn get_authn_method(&self) -> ClientAuthnMethod {
+        self.get_client_data().authn_method
+    }
+
+    /// Get the key this client signs its client assertions with, if it authenticates
+    /// with `ClientAuthnMethod::PrivateKeyJwt`.  Its public key must be registered with
+    /// the AuthzServer.
+    ///
+    /// The default implementation returns None.
+    fn get_client_assertion_key<'a>(&'a self) -> Option<&'a JwsKey> {
+        None
+    }
+
+    /// Create a client assertion to authenticate with at an AuthzServer endpoint
+    /// (RFC 7523 section 2.2), for clients using `ClientAuthnMethod::ClientSecretJwt`
+    /// or `ClientAuthnMethod::PrivateKeyJwt`.  `audience` is the URL of the endpoint.
+    fn generate_client_assertion(&self, audience: &str) -> Result<String, OAuthError> {
+        let client_data = self.get_client_data();
+        match self.get_authn_method() {
+            ClientAuthnMethod::ClientSecretJwt => {
+                let key = HmacKey {
+                    key: client_data.credentials.as_bytes().to_vec(),
+                    key_id: None,
+                };
+                sign_client_assertion(&client_data.client_id, audience, &key)
+            },
+            ClientAuthnMethod::PrivateKeyJwt => match self.get_client_assertion_key() {
+                Some(key) => sign_client_assertion(&client_data.client_id, audience, key),
+                None => Err(OAuthError::ClientKeyMissing),
+            },
+            _ => Err(OAuthError::ClientAuthnMethodMismatch),
+        }
+    }
+
     /// This is the starting point for the OAuth sequence.  It redirects the user-agent
     /// to the AuthzServer's authz_request endpoint
+    ///
+    /// A PKCE (RFC 7636) code verifier is generated and stored, and the S256 code
+    /// challenge derived from it is sent with the request.
     fn start_oauth(&mut self, scope: Option<String>, mut authz_request_url: Url,
                    mut response: Response)
     {
@@ -40,12 +107,17 @@ pub trait Client
             client_data.client_id.clone()
         };
         let state = self.generate_nonce();
+        let code_verifier = self.generate_code_verifier(&*state);
+        let code_challenge = CodeChallenge::from_verifier(&*code_verifier,
+                                                          CodeChallengeMethod::S256);
 
         authz_request_url.query_pairs_mut()
             .append_pair("response_type", "code")
             .append_pair("client_id", &*client_id)
             .append_pair("redirect_uri", self.get_redirect_uri())
-            .append_pair("state", &*state);
+            .append_pair("state", &*state)
+            .append_pair("code_challenge", &*code_challenge.challenge)
+            .append_pair("code_challenge_method", &*format!("{}", code_challenge.method));
 
         if let Some(s) = scope {
             authz_request_url.query_pairs_mut()
@@ -58,6 +130,65 @@ pub trait Client
         let _ = streaming_response.end();
     }
 
+    /// This is an alternative starting point for the OAuth sequence, which first
+    /// pushes the authorization request to the AuthzServer's pushed authorization
+    /// request endpoint (RFC 9126), so that its parameters are authenticated and are
+    /// not exposed to the user-agent.  It then redirects the user-agent to the
+    /// AuthzServer's authz_request endpoint with just the returned `request_uri`.
+    ///
+    /// If the AuthzServer rejects the request, the user-agent is not redirected and
+    /// the error is returned instead.
+    fn push_authz_request(&mut self, scope: Option<String>, authz_par_url: Url,
+                          mut authz_request_url: Url, mut response: Response)
+                          -> Result<Result<(), AuthzError>, OAuthError>
+    {
+        let client_id = {
+            let client_data = self.get_client_data();
+            client_data.client_id.clone()
+        };
+        let state = self.generate_nonce();
+        let code_verifier = self.generate_code_verifier(&*state);
+        let code_challenge = CodeChallenge::from_verifier(&*code_verifier,
+                                                          CodeChallengeMethod::S256);
+
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("response_type", "code")
+            .append_pair("redirect_uri", self.get_redirect_uri())
+            .append_pair("state", &*state)
+            .append_pair("code_challenge", &*code_challenge.challenge)
+            .append_pair("code_challenge_method", &*format!("{}", code_challenge.method));
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        let (status, bodystr) = try!(authenticated_post(self, authz_par_url, &*body));
+        let request_uri = match status {
+            StatusCode::Created => {
+                let json: Value = try!(::serde_json::from_str(&bodystr));
+                match json.find("request_uri").and_then(|v| v.as_string()) {
+                    Some(r) => r.to_owned(),
+                    None => return Err(OAuthError::AuthzRequestUriInvalid),
+                }
+            },
+            StatusCode::BadRequest => {
+                let authz_error: AuthzError = try!(::serde_json::from_str(&bodystr));
+                return Ok(Err(authz_error));
+            },
+            _ => return Err(OAuthError::UnexpectedStatusCode),
+        };
+
+        authz_request_url.query_pairs_mut()
+            .append_pair("client_id", &*client_id)
+            .append_pair("request_uri", &*request_uri);
+
+        response.headers_mut().set(Location(authz_request_url.into_string()));
+        *response.status_mut() = StatusCode::Found;
+        let streaming_response = response.start().unwrap();
+        let _ = streaming_response.end();
+        Ok(Ok(()))
+    }
+
     /// Handle an HTTP request to the Redirect URL (from the user-agent)
     ///
     /// Refer to rfc6749 section 3.1.2 as to the requirements of this endpoint
@@ -91,41 +222,33 @@ pub trait Client
         };
 
         // Require state
-        match state {
+        let state = match state {
             None => return Err(OAuthError::ClientStateMissing),
             Some(s) => {
                 if ! self.consume_nonce(&*s) {
                     return Err(OAuthError::ClientNonceMismatch);
                 }
+                s
             }
-        }
+        };
 
-        let client_data = self.get_client_data();
+        // Get the PKCE code verifier generated for this state
+        let code_verifier = self.consume_code_verifier(&*state);
 
-        let client_id = percent_encode(client_data.client_id.as_bytes(),
-                                       QUERY_ENCODE_SET).collect::<String>();
         let code = percent_encode(code.as_bytes(),
                                   QUERY_ENCODE_SET).collect::<String>();
         let redirect_uri = percent_encode(self.get_redirect_uri().as_bytes(),
                                           QUERY_ENCODE_SET).collect::<String>();
 
-        let body = format!("grant_type=authorization_code&code={}&redirect_uri={}&client_id={}",
-                           code, redirect_uri, client_id);
-
-        let hyper = ::hyper::client::Client::new();
-        let mut res = hyper.post(authz_token_url)
-            .header(Authorization(Basic {
-                username: (*client_data.client_id).clone(),
-                password: Some(client_data.credentials.clone())
-            }))
-            .body(&*body)
-            .send().unwrap();
+        let mut body = format!("grant_type=authorization_code&code={}&redirect_uri={}",
+                               code, redirect_uri);
+        if let Some(cv) = code_verifier {
+            body.push_str(&*format!("&code_verifier={}", cv));
+        }
 
-        let mut body: Vec<u8> = Vec::new();
-        try!(res.read_to_end(&mut body));
-        let bodystr = try!(String::from_utf8(body));
+        let (status, bodystr) = try!(authenticated_post(self, authz_token_url, &*body));
 
-        match res.status {
+        match status {
             StatusCode::Ok => {
                 let token_data: TokenData = ::serde_json::from_str(&bodystr).unwrap();
                 return Ok(Ok(token_data));
@@ -139,4 +262,304 @@ pub trait Client
             },
         }
     }
+
+    /// Obtain a new access token from the AuthzServer's token endpoint using a refresh
+    /// token that was previously issued (rfc6749 section 6).  `scope` may be supplied
+    /// to narrow the scope of the new access token, but it cannot exceed the scope that
+    /// was originally granted.
+    ///
+    /// If the AuthzServer rotates refresh tokens, the returned `TokenData` will contain
+    /// a new refresh token which you should store in place of the old one.
+    fn refresh_access_token(&self, refresh_token: &str, scope: Option<String>,
+                            authz_token_url: Url)
+                            -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", "refresh_token")
+            .append_pair("refresh_token", refresh_token);
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        token_request(self, authz_token_url, &*body)
+    }
+
+    /// Obtain an access token from the AuthzServer's token endpoint on the client's own
+    /// behalf, using the client credentials grant (rfc6749 section 4.4).  There is no
+    /// resource owner or user-agent involved.  This is only available to confidential
+    /// clients.
+    fn request_client_credentials_token(&self, scope: Option<String>, authz_token_url: Url)
+                                        -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", "client_credentials");
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        token_request(self, authz_token_url, &*body)
+    }
+
+    /// Start the device authorization grant (RFC 8628), for devices which cannot
+    /// receive redirects, such as TVs and command line applications.  Display the
+    /// returned `user_code` and `verification_uri` (or `verification_uri_complete`) to
+    /// the user, and then call `poll_device_token()`.
+    fn request_device_authorization(&self, scope: Option<String>, authz_device_url: Url)
+                                    -> Result<Result<DeviceAuthorization, TokenError>,
+                                              OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        let (status, bodystr) = try!(authenticated_post(self, authz_device_url, &*body));
+        match status {
+            StatusCode::Ok => {
+                let device_authorization: DeviceAuthorization =
+                    try!(::serde_json::from_str(&bodystr));
+                Ok(Ok(device_authorization))
+            },
+            StatusCode::BadRequest | StatusCode::Unauthorized => {
+                let token_error: TokenError = try!(::serde_json::from_str(&bodystr));
+                Ok(Err(token_error))
+            },
+            _ => Err(OAuthError::UnexpectedStatusCode),
+        }
+    }
+
+    /// Poll the AuthzServer's token endpoint until the user approves or denies the
+    /// device authorization, or it expires (RFC 8628 section 3.4).  This blocks,
+    /// waiting `interval` seconds between polls, and 5 seconds longer each time the
+    /// AuthzServer asks the device to slow down.
+    fn poll_device_token(&self, device_authorization: &DeviceAuthorization,
+                         authz_token_url: Url)
+                         -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", DEVICE_CODE_GRANT_TYPE)
+            .append_pair("device_code", &*device_authorization.device_code);
+        let body = serializer.finish();
+
+        poll_token_request(self, authz_token_url, &*body,
+                           device_authorization.interval.unwrap_or(5),
+                           device_authorization.expires_in)
+    }
+
+    /// Start a backchannel authentication request (CIBA), to have the user identified
+    /// by `request.login_hint` authenticate and approve the request on their own
+    /// device.  Show the user the `binding_message`, if any.  Then, depending on the
+    /// client's delivery mode, call `poll_backchannel_token()`, or wait for a
+    /// notification at the client's notification endpoint.
+    fn request_backchannel_authentication(&self, request: &BackchannelAuthRequest,
+                                          authz_backchannel_url: Url)
+                                          -> Result<Result<BackchannelAuthResponse,
+                                                           TokenError>, OAuthError>
+    {
+        let (status, bodystr) = try!(authenticated_post(self, authz_backchannel_url,
+                                                        &*request.to_form()));
+        match status {
+            StatusCode::Ok => {
+                let backchannel_response: BackchannelAuthResponse =
+                    try!(::serde_json::from_str(&bodystr));
+                Ok(Ok(backchannel_response))
+            },
+            StatusCode::BadRequest | StatusCode::Unauthorized => {
+                let token_error: TokenError = try!(::serde_json::from_str(&bodystr));
+                Ok(Err(token_error))
+            },
+            _ => Err(OAuthError::UnexpectedStatusCode),
+        }
+    }
+
+    /// Poll the AuthzServer's token endpoint until the user approves or denies the
+    /// backchannel authentication request, or it expires (CIBA Core section 10.1).  In
+    /// ping mode, call this once notified, and the first request is made immediately
+    /// rather than after the polling interval.  This blocks as `poll_device_token()`
+    /// does.
+    fn poll_backchannel_token(&self, backchannel_response: &BackchannelAuthResponse,
+                              authz_token_url: Url)
+                              -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", CIBA_GRANT_TYPE)
+            .append_pair("auth_req_id", &*backchannel_response.auth_req_id);
+        let body = serializer.finish();
+
+        // The ping notification says the result is ready (CIBA Core section 10.2)
+        if self.get_client_data().backchannel_delivery_mode == Some(DeliveryMode::Ping) {
+            match try!(token_request(self, authz_token_url.clone(), &*body)) {
+                Err(ref e) if e.error == TokenErrorCode::AuthorizationPending ||
+                    e.error == TokenErrorCode::SlowDown => {},
+                result => return Ok(result),
+            }
+        }
+
+        poll_token_request(self, authz_token_url, &*body,
+                           backchannel_response.interval.unwrap_or(5),
+                           backchannel_response.expires_in)
+    }
+
+    /// Request an access token with a JWT assertion (RFC 7523 section 2.1), such as one
+    /// made with `jwt_bearer::sign_jwt_assertion()` for a service account, rather than
+    /// going through a browser redirect.
+    fn request_jwt_bearer_token(&self, assertion: &str, scope: Option<String>,
+                                authz_token_url: Url)
+                                -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", JWT_BEARER_GRANT_TYPE)
+            .append_pair("assertion", assertion);
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        token_request(self, authz_token_url, &*body)
+    }
+
+    /// Request an access token with a SAML 2.0 assertion (RFC 7522 section 2.1), such as
+    /// one issued by an enterprise identity provider.  `assertion` is the XML of the
+    /// assertion, which is base64url encoded for sending.
+    fn request_saml2_bearer_token(&self, assertion: &[u8], scope: Option<String>,
+                                  authz_token_url: Url)
+                                  -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", SAML2_BEARER_GRANT_TYPE)
+            .append_pair("assertion", &*encode_saml2_assertion(assertion));
+        if let Some(ref s) = scope {
+            serializer.append_pair("scope", s);
+        }
+        let body = serializer.finish();
+
+        token_request(self, authz_token_url, &*body)
+    }
+
+    /// Exchange a token for another at the AuthzServer's token endpoint (RFC 8693),
+    /// such as a user's access token for a narrower token to call a downstream service
+    /// with.  The type of the issued token is in `TokenData.issued_token_type`.
+    fn exchange_token(&self, request: &TokenExchangeRequest, authz_token_url: Url)
+                      -> Result<Result<TokenData, TokenError>, OAuthError>
+    {
+        token_request(self, authz_token_url, &*request.to_form())
+    }
+
+    /// Notify the AuthzServer's revocation endpoint that an access token or refresh
+    /// token is no longer needed (RFC 7009), for instance when the user logs out.
+    /// `token_type_hint` may be supplied to help the AuthzServer find the token.
+    ///
+    /// Revoking a token which is unknown to the AuthzServer is not an error.
+    fn revoke_token(&self, token: &str, token_type_hint: Option<TokenTypeHint>,
+                    authz_revocation_url: Url) -> Result<Result<(), TokenError>, OAuthError>
+    {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("token", token);
+        if let Some(hint) = token_type_hint {
+            serializer.append_pair("token_type_hint", &*format!("{}", hint));
+        }
+        let body = serializer.finish();
+
+        let (status, bodystr) = try!(authenticated_post(self, authz_revocation_url, &*body));
+        match status {
+            StatusCode::Ok => Ok(Ok(())),
+            StatusCode::BadRequest | StatusCode::Unauthorized => {
+                let token_error: TokenError = try!(::serde_json::from_str(&bodystr));
+                Ok(Err(token_error))
+            },
+            _ => Err(OAuthError::UnexpectedStatusCode),
+        }
+    }
+}
+
+/// POST url-encoded parameters directly to an AuthzServer endpoint, authenticating by
+/// the method the client picks with `get_authn_method()`, and return the response
+/// status and body.
+fn authenticated_post<T: Client + ?Sized>(client: &T, url: Url, body: &str)
+                                          -> Result<(StatusCode, String), OAuthError>
+{
+    let client_data = client.get_client_data();
+    let authn_method = client.get_authn_method();
+    let mut serializer = Serializer::new(body.to_owned());
+    match authn_method {
+        ClientAuthnMethod::ClientSecretJwt | ClientAuthnMethod::PrivateKeyJwt => {
+            let assertion = try!(client.generate_client_assertion(url.as_str()));
+            serializer.append_pair("client_assertion_type", CLIENT_ASSERTION_TYPE)
+                .append_pair("client_assertion", &*assertion);
+        },
+        // Public clients cannot authenticate, and instead identify themselves with the
+        // client_id parameter (rfc6749 section 2.3)
+        ClientAuthnMethod::None => {
+            serializer.append_pair("client_id", &*client_data.client_id);
+        },
+        ClientAuthnMethod::ClientSecretPost => {
+            serializer.append_pair("client_id", &*client_data.client_id)
+                .append_pair("client_secret", &*client_data.credentials);
+        },
+        ClientAuthnMethod::ClientSecretBasic => {},
+    }
+    let body = serializer.finish();
+
+    let hyper = ::hyper::client::Client::new();
+    let mut request = hyper.post(url).header(ContentType::form_url_encoded());
+    if authn_method == ClientAuthnMethod::ClientSecretBasic {
+        request = request.header(client_data.http_basic_authentication_generate());
+    }
+    let mut res = try!(request.body(&*body).send());
+
+    let mut body: Vec<u8> = Vec::new();
+    try!(res.read_to_end(&mut body));
+    let bodystr = try!(String::from_utf8(body));
+
+    Ok((res.status, bodystr))
+}
+
+/// POST a request to the AuthzServer's token endpoint, authenticating as in
+/// `authenticated_post()`, and parse the response as either `TokenData` or `TokenError`.
+fn token_request<T: Client + ?Sized>(client: &T, authz_token_url: Url, body: &str)
+                                     -> Result<Result<TokenData, TokenError>, OAuthError>
+{
+    let (status, bodystr) = try!(authenticated_post(client, authz_token_url, body));
+
+    match status {
+        StatusCode::Ok => {
+            let token_data: TokenData = try!(::serde_json::from_str(&bodystr));
+            Ok(Ok(token_data))
+        },
+        StatusCode::BadRequest | StatusCode::Unauthorized => {
+            let token_error: TokenError = try!(::serde_json::from_str(&bodystr));
+            Ok(Err(token_error))
+        },
+        _ => Err(OAuthError::UnexpectedStatusCode),
+    }
+}
+
+/// Repeat a request to the AuthzServer's token endpoint, as in `token_request()`, until
+/// it is no longer pending or `expires_in` seconds have passed, waiting `interval`
+/// seconds between requests, and 5 seconds longer each time the AuthzServer asks the
+/// client to slow down (RFC 8628 section 3.5).
+fn poll_token_request<T: Client + ?Sized>(client: &T, authz_token_url: Url, body: &str,
+                                          interval: u64, expires_in: u64)
+                                          -> Result<Result<TokenData, TokenError>, OAuthError>
+{
+    let mut interval = interval;
+    let deadline = ::time::get_time().sec as u64 + expires_in;
+    loop {
+        ::std::thread::sleep(Duration::from_secs(interval));
+        match try!(token_request(client, authz_token_url.clone(), body)) {
+            Err(ref e) if e.error == TokenErrorCode::AuthorizationPending => {},
+            Err(ref e) if e.error == TokenErrorCode::SlowDown => interval += 5,
+            result => return Ok(result),
+        }
+        if ::time::get_time().sec as u64 >= deadline {
+            return Ok(Err(TokenError {
+                error: TokenErrorCode::ExpiredToken,
+                error_description: None,
+                error_uri: None,
+            }));
+        }
+    }
 }
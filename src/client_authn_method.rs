@@ -0,0 +1,132 @@
+
+use std::fmt;
+use std::fmt::Display;
+use jwt::{Jwt, JwsKey};
+use jwk::KeySet;
+use jwt_bearer::{sign_jwt_assertion, verify_jwt_assertion};
+use {ClientId, OAuthError};
+
+/// The client_assertion_type value for JWT client assertions (RFC 7523 section 2.2)
+pub const CLIENT_ASSERTION_TYPE: &'static str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Lifetime of the client assertions made by `sign_client_assertion()`, in seconds
+pub const CLIENT_ASSERTION_LIFETIME: u64 = 60;
+
+/// Longest lifetime accepted for a client assertion, in seconds.  This bounds how long
+/// the Authorization Server must remember `jti` values to detect replays.
+pub const MAX_CLIENT_ASSERTION_LIFETIME: u64 = 300;
+
+/// How a client authenticates to the token endpoint (and the other endpoints it calls
+/// directly).  Registered for each client as its `token_endpoint_auth_method`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientAuthnMethod {
+    /// HTTP Basic authentication with the client secret (RFC 6749 section 2.3.1)
+    ClientSecretBasic,
+    /// The client secret in the request body, as the `client_secret` parameter along
+    /// with `client_id` (RFC 6749 section 2.3.1)
+    ClientSecretPost,
+    /// A JWT client assertion signed with the client secret (HS256)
+    ClientSecretJwt,
+    /// A JWT client assertion signed with one of the client's registered keys
+    PrivateKeyJwt,
+    /// The client does not authenticate, and identifies itself with the `client_id`
+    /// parameter.  Only for public clients.
+    None,
+}
+
+impl ClientAuthnMethod {
+    /// Parse a `token_endpoint_auth_method`, returning None for unknown methods
+    pub fn from_str(method: &str) -> Option<ClientAuthnMethod> {
+        match method {
+            "client_secret_basic" => Some(ClientAuthnMethod::ClientSecretBasic),
+            "client_secret_post" => Some(ClientAuthnMethod::ClientSecretPost),
+            "client_secret_jwt" => Some(ClientAuthnMethod::ClientSecretJwt),
+            "private_key_jwt" => Some(ClientAuthnMethod::PrivateKeyJwt),
+            "none" => Some(ClientAuthnMethod::None),
+            _ => None,
+        }
+    }
+
+    /// Whether the client authenticates with a JWT client assertion
+    pub fn uses_assertion(&self) -> bool {
+        *self == ClientAuthnMethod::ClientSecretJwt || *self == ClientAuthnMethod::PrivateKeyJwt
+    }
+}
+
+impl Display for ClientAuthnMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            ClientAuthnMethod::ClientSecretBasic => write!(f, "client_secret_basic"),
+            ClientAuthnMethod::ClientSecretPost => write!(f, "client_secret_post"),
+            ClientAuthnMethod::ClientSecretJwt => write!(f, "client_secret_jwt"),
+            ClientAuthnMethod::PrivateKeyJwt => write!(f, "private_key_jwt"),
+            ClientAuthnMethod::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Create a client assertion (RFC 7523 section 3) for `client_id` to authenticate with:
+/// a JWT issued by and about the client, for `audience` (the URL of the endpoint it is
+/// sent to, or the Authorization Server's issuer identifier).
+pub fn sign_client_assertion(client_id: &ClientId, audience: &str, key: &JwsKey)
+                             -> Result<String, OAuthError>
+{
+    sign_jwt_assertion(&*client_id, &*client_id, audience, CLIENT_ASSERTION_LIFETIME, key)
+}
+
+/// Verify a client assertion sent by `client_id`: as for `verify_jwt_assertion()`, and
+/// the subject must also be the client.  It must have a `jti`, for the caller to check
+/// for replays, and must expire within `MAX_CLIENT_ASSERTION_LIFETIME` of being issued.
+pub fn verify_client_assertion(assertion: &str, client_id: &ClientId, key_set: &KeySet,
+                               audiences: &[&str]) -> Result<Jwt, OAuthError>
+{
+    let jwt = try!(verify_jwt_assertion(assertion, &*client_id, key_set, audiences));
+    if jwt.claim_str("sub") != Some(&***client_id) || jwt.claim_str("jti").is_none() {
+        return Err(OAuthError::JwtClaimsInvalid);
+    }
+
+    // Measure the lifetime from `iat`, or from now if it is missing or in the future
+    let now = ::time::get_time().sec as u64;
+    let issued = ::std::cmp::min(jwt.claim_u64("iat").unwrap_or(now), now);
+    let exp = jwt.claim_u64("exp").unwrap_or(0);
+    if exp.saturating_sub(issued) > MAX_CLIENT_ASSERTION_LIFETIME {
+        return Err(OAuthError::JwtClaimsInvalid);
+    }
+    Ok(jwt)
+}
+
+#[test]
+fn test_client_assertion() {
+    use jwt::HmacKey;
+
+    let keys = vec![HmacKey { key: b"secret".to_vec(), key_id: None }];
+    let client_id = ClientId("s6BhdRkqt3".to_owned());
+    let assertion = sign_client_assertion(&client_id, "https://server.example.com/token",
+                                          &keys[0]).unwrap();
+
+    let jwt = verify_client_assertion(&*assertion, &client_id, &keys,
+                                      &["https://server.example.com",
+                                        "https://server.example.com/token"]).unwrap();
+    assert_eq!(jwt.claim_str("iss"), Some("s6BhdRkqt3"));
+
+    assert!(verify_client_assertion(&*assertion, &ClientId("other".to_owned()), &keys,
+                                    &["https://server.example.com/token"]).is_err());
+
+    // A JWT bearer assertion about someone else does not authenticate the client
+    let other = sign_jwt_assertion("s6BhdRkqt3", "mailto:mike@example.com",
+                                   "https://server.example.com/token", 60, &keys[0]).unwrap();
+    assert!(verify_client_assertion(&*other, &client_id, &keys,
+                                    &["https://server.example.com/token"]).is_err());
+
+    // Long-lived assertions are refused, as their jti would have to be kept for long
+    let long = sign_jwt_assertion("s6BhdRkqt3", "s6BhdRkqt3", "https://server.example.com/token",
+                                  MAX_CLIENT_ASSERTION_LIFETIME + 60, &keys[0]).unwrap();
+    assert!(verify_client_assertion(&*long, &client_id, &keys,
+                                    &["https://server.example.com/token"]).is_err());
+
+    assert_eq!(ClientAuthnMethod::from_str("private_key_jwt"),
+               Some(ClientAuthnMethod::PrivateKeyJwt));
+    assert_eq!(format!("{}", ClientAuthnMethod::ClientSecretJwt), "client_secret_jwt");
+}
@@ -0,0 +1,45 @@
+
+use {AuthzRequest, ClientId, RedirectUri, CodeChallenge};
+
+/// The data that must be stored with an authorization code when it is issued, and
+/// returned from `AuthzServer::retrieve_client_authorization()` when the client
+/// exchanges the code for an access token.
+#[derive(Clone, Debug)]
+pub struct ClientAuthorization {
+    /// The client the code was issued to
+    pub client_id: ClientId,
+
+    /// The redirect_uri the code was sent to
+    pub redirect_uri: RedirectUri,
+
+    /// The scope granted.  If this includes `openid`, an ID token is issued along
+    /// with the access token.
+    pub scope: Option<String>,
+
+    /// PKCE code challenge (RFC 7636) from the authorization request, if any
+    pub code_challenge: Option<CodeChallenge>,
+
+    /// OpenID Connect nonce from the authorization request, if any
+    pub nonce: Option<String>,
+
+    /// OpenID Connect max_age from the authorization request, if any
+    pub max_age: Option<u64>,
+}
+
+impl ClientAuthorization {
+    /// Build the authorization data for a granted request, which was redirected to
+    /// `redirect_uri` (as returned by `AuthzServer::resolve_redirect_uri()`), granting
+    /// the scope requested.
+    pub fn from_request(request: &AuthzRequest, redirect_uri: RedirectUri)
+                        -> ClientAuthorization
+    {
+        ClientAuthorization {
+            client_id: request.client_id.clone(),
+            redirect_uri: redirect_uri,
+            scope: request.scope.clone(),
+            code_challenge: request.code_challenge.clone(),
+            nonce: request.nonce.clone(),
+            max_age: request.max_age,
+        }
+    }
+}
@@ -2,7 +2,7 @@
 use std::str::Utf8Error;
 use url::percent_encoding::{QUERY_ENCODE_SET, percent_encode, percent_decode};
 use hyper::header::{Authorization, Basic};
-use {ClientType, ClientId, RedirectUri};
+use {ClientType, ClientAuthnMethod, ClientId, RedirectUri, DeliveryMode};
 
 /// Client data is registered with the Authorization Service prior to the OAuth 2.0
 /// protocol commencing.  This can be done with config files for well-known clients.
@@ -26,9 +26,34 @@ pub struct ClientData {
     /// Client Credentials, serialized.  Required, but the details are out of scope.
     pub credentials: String,
 
-    /// Authentication Scheme, serialized.  Only required if multiple authentication schemes
-    /// are implemented and the server needs to know which one this client is using.
-    pub authn_scheme: Option<String>,
+    /// Scope the client is allowed to request, as a space-delimited list of scope tokens.
+    /// If None, the Authorization Server does not restrict the scope by client.  This is
+    /// also the default scope for the client credentials, password, JWT bearer and SAML
+    /// 2.0 bearer grants, and for device authorization requests, if the client does not
+    /// request a scope.
+    pub scope: Option<String>,
+
+    /// How the client authenticates at the token endpoint.  Public clients use
+    /// `ClientAuthnMethod::None`.
+    pub authn_method: ClientAuthnMethod,
+
+    /// Whether this client must push its authorization requests to the pushed
+    /// authorization request endpoint (RFC 9126) rather than sending them via the
+    /// user-agent.
+    pub par_required: bool,
+
+    /// Whether this client must sign its authorization requests as request objects
+    /// (RFC 9101), rather than sending plain parameters.  Its request objects must then
+    /// also have `exp` and an `aud` including the issuer identifier.
+    pub request_object_required: bool,
+
+    /// How the results of backchannel authentication requests (CIBA) are delivered to
+    /// this client.  If None, the client may not make such requests.
+    pub backchannel_delivery_mode: Option<DeliveryMode>,
+
+    /// The client's endpoint for backchannel authentication notifications, required
+    /// for the ping and push delivery modes.
+    pub backchannel_notification_endpoint: Option<String>,
 }
 
 impl ClientData {
@@ -0,0 +1,385 @@
+
+use url::{Url, Host};
+use json::{push_json_string, push_json_strings, push_json_number, push_json_bool};
+use syntax::valid_scope_str;
+use {ClientData, ClientId, ClientType, ClientAuthnMethod, RedirectUri, DeliveryMode};
+
+/// Client Metadata, as submitted by a client registering itself with the Authorization
+/// Server (RFC 7591 section 2).  All fields are optional at registration time; the
+/// Authorization Server fills in defaults for those that have them.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClientMetadata {
+    /// Redirection URIs for use in redirect-based flows
+    pub redirect_uris: Option<Vec<String>>,
+    /// Requested client authentication method for the token endpoint.  Defaults to
+    /// "client_secret_basic".
+    pub token_endpoint_auth_method: Option<String>,
+    /// Grant types the client will restrict itself to.  Defaults to
+    /// ["authorization_code"].
+    pub grant_types: Option<Vec<String>>,
+    /// Response types the client will restrict itself to.  Defaults to ["code"].
+    pub response_types: Option<Vec<String>>,
+    /// Human-readable name of the client
+    pub client_name: Option<String>,
+    /// URL of the home page of the client
+    pub client_uri: Option<String>,
+    /// URL of the logo of the client
+    pub logo_uri: Option<String>,
+    /// Space-delimited scope values the client can use when requesting access tokens
+    pub scope: Option<String>,
+    /// Ways to contact people responsible for the client, typically email addresses
+    pub contacts: Option<Vec<String>>,
+    /// URL of the terms of service of the client
+    pub tos_uri: Option<String>,
+    /// URL of the policy document describing how the client uses data
+    pub policy_uri: Option<String>,
+    /// URL of the client's JSON Web Key Set document
+    pub jwks_uri: Option<String>,
+    /// Identifier for the software that the client is running
+    pub software_id: Option<String>,
+    /// Version of the software that the client is running
+    pub software_version: Option<String>,
+    /// Whether the client must use pushed authorization requests (RFC 9126 section 6).
+    /// Defaults to false.
+    pub require_pushed_authorization_requests: Option<bool>,
+    /// Whether the client must sign its authorization requests as request objects
+    /// (RFC 9101 section 10.5).  Defaults to false.
+    pub require_signed_request_object: Option<bool>,
+    /// How the results of backchannel authentication requests are delivered to the
+    /// client: "poll", "ping" or "push" (CIBA Core section 4)
+    pub backchannel_token_delivery_mode: Option<String>,
+    /// The client's endpoint for backchannel authentication notifications, required for
+    /// the ping and push delivery modes
+    pub backchannel_client_notification_endpoint: Option<String>,
+}
+
+impl ClientMetadata {
+    /// Validate the metadata submitted by a client, returning the metadata with
+    /// defaults filled in for any fields which have them (RFC 7591 section 2).
+    pub fn validated(mut self) -> Result<ClientMetadata, RegistrationError> {
+        // Redirect URIs must be absolute URIs without a fragment (rfc6749 section 3.1.2)
+        // and with a scheme safe to redirect to.  Clients which do not authenticate are
+        // taken to be native apps, which may also use private-use schemes.
+        let native = self.client_type() == ClientType::PublicClient;
+        if let Some(ref uris) = self.redirect_uris {
+            for uri in uris {
+                match Url::parse(uri) {
+                    Ok(ref url) if url.fragment().is_none() &&
+                        valid_redirect_scheme(url, native) => {},
+                    _ => return Err(RegistrationError {
+                        error: RegistrationErrorCode::InvalidRedirectUri,
+                        error_description: Some(format!("Invalid redirect URI: {}", uri)),
+                    }),
+                }
+            }
+        }
+
+        if self.token_endpoint_auth_method.is_none() {
+            self.token_endpoint_auth_method = Some("client_secret_basic".to_owned());
+        }
+        match ClientAuthnMethod::from_str(self.token_endpoint_auth_method.as_ref().unwrap()) {
+            None => return Err(metadata_error("Unsupported token_endpoint_auth_method")),
+            Some(ClientAuthnMethod::PrivateKeyJwt) if self.jwks_uri.is_none() =>
+                return Err(metadata_error("private_key_jwt requires jwks_uri")),
+            Some(_) => {},
+        }
+        if self.grant_types.is_none() {
+            self.grant_types = Some(vec!["authorization_code".to_owned()]);
+        }
+        if self.response_types.is_none() {
+            self.response_types = Some(vec!["code".to_owned()]);
+        }
+
+        // grant_types and response_types must be consistent (RFC 7591 section 2.1)
+        {
+            let grant_types = self.grant_types.as_ref().unwrap();
+            let has_grant = |gt: &str| grant_types.iter().any(|g| &**g == gt);
+            for rt in self.response_types.as_ref().unwrap() {
+                match &**rt {
+                    "code" if has_grant("authorization_code") => {},
+                    "token" if has_grant("implicit") => {},
+                    _ => return Err(metadata_error(
+                        "response_types are inconsistent with grant_types")),
+                }
+            }
+
+            // Redirect-based flows require a redirect URI
+            if has_grant("authorization_code") || has_grant("implicit") {
+                if self.redirect_uris.as_ref().map(|u| u.is_empty()).unwrap_or(true) {
+                    return Err(RegistrationError {
+                        error: RegistrationErrorCode::InvalidRedirectUri,
+                        error_description: Some("redirect_uris are required for \
+                                                 redirect-based grant types".to_owned()),
+                    });
+                }
+            }
+
+            // CIBA Core section 4: ping and push modes need a notification endpoint
+            if let Some(ref mode) = self.backchannel_token_delivery_mode {
+                match DeliveryMode::from_str(mode) {
+                    None => return Err(metadata_error(
+                        "Unsupported backchannel_token_delivery_mode")),
+                    Some(DeliveryMode::Poll) => {},
+                    Some(_) => match self.backchannel_client_notification_endpoint {
+                        Some(ref e) if e.starts_with("https://") => {},
+                        _ => return Err(metadata_error(
+                            "backchannel_client_notification_endpoint must be an https URL")),
+                    },
+                }
+            }
+
+            // rfc6749 section 4.4: only confidential clients may use client credentials
+            if has_grant("client_credentials") &&
+                self.client_type() != ClientType::ConfidentialClient
+            {
+                return Err(metadata_error(
+                    "client_credentials grant type requires a confidential client"));
+            }
+        }
+
+        if let Some(ref scope) = self.scope {
+            if ! valid_scope_str(scope) {
+                return Err(metadata_error("Invalid scope"));
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// The type of client these metadata describe.  Clients which do not authenticate
+    /// at the token endpoint are public; all others are confidential.
+    pub fn client_type(&self) -> ClientType {
+        match self.authn_method() {
+            ClientAuthnMethod::None => ClientType::PublicClient,
+            _ => ClientType::ConfidentialClient,
+        }
+    }
+
+    /// The client authentication method these metadata describe, defaulting to
+    /// `ClientSecretBasic`
+    pub fn authn_method(&self) -> ClientAuthnMethod {
+        self.token_endpoint_auth_method.as_ref()
+            .and_then(|m| ClientAuthnMethod::from_str(m))
+            .unwrap_or(ClientAuthnMethod::ClientSecretBasic)
+    }
+
+    /// Build the `ClientData` for a client registered with these metadata
+    pub fn to_client_data(&self, client_id: &ClientId, client_secret: Option<&str>)
+                          -> ClientData
+    {
+        ClientData {
+            client_id: client_id.clone(),
+            client_type: self.client_type(),
+            redirect_uri: self.redirect_uris.as_ref().unwrap_or(&vec![]).iter()
+                .map(|u| RedirectUri(u.clone())).collect(),
+            credentials: client_secret.unwrap_or("").to_owned(),
+            scope: self.scope.clone(),
+            authn_method: self.authn_method(),
+            par_required: self.require_pushed_authorization_requests.unwrap_or(false),
+            request_object_required: self.require_signed_request_object.unwrap_or(false),
+            backchannel_delivery_mode: self.backchannel_token_delivery_mode.as_ref()
+                .and_then(|m| DeliveryMode::from_str(m)),
+            backchannel_notification_endpoint:
+                self.backchannel_client_notification_endpoint.clone(),
+        }
+    }
+
+    /// Append the metadata fields which are Some to a JSON object under construction
+    fn push_json(&self, json_str: &mut String) {
+        push_json_strings(json_str, "redirect_uris", &self.redirect_uris);
+        push_json_string(json_str, "token_endpoint_auth_method",
+                         &self.token_endpoint_auth_method);
+        push_json_strings(json_str, "grant_types", &self.grant_types);
+        push_json_strings(json_str, "response_types", &self.response_types);
+        push_json_string(json_str, "client_name", &self.client_name);
+        push_json_string(json_str, "client_uri", &self.client_uri);
+        push_json_string(json_str, "logo_uri", &self.logo_uri);
+        push_json_string(json_str, "scope", &self.scope);
+        push_json_strings(json_str, "contacts", &self.contacts);
+        push_json_string(json_str, "tos_uri", &self.tos_uri);
+        push_json_string(json_str, "policy_uri", &self.policy_uri);
+        push_json_string(json_str, "jwks_uri", &self.jwks_uri);
+        push_json_string(json_str, "software_id", &self.software_id);
+        push_json_string(json_str, "software_version", &self.software_version);
+        push_json_bool(json_str, "require_pushed_authorization_requests",
+                       &self.require_pushed_authorization_requests);
+        push_json_bool(json_str, "require_signed_request_object",
+                       &self.require_signed_request_object);
+        push_json_string(json_str, "backchannel_token_delivery_mode",
+                         &self.backchannel_token_delivery_mode);
+        push_json_string(json_str, "backchannel_client_notification_endpoint",
+                         &self.backchannel_client_notification_endpoint);
+    }
+}
+
+/// Whether a redirect URI uses a scheme we are willing to redirect to: https, http to
+/// a loopback address, or for native clients a private-use scheme in reverse domain name
+/// form (RFC 8252 sections 7.1 and 7.3).  This rules out schemes such as `javascript:`
+/// and `data:`.
+fn valid_redirect_scheme(url: &Url, native: bool) -> bool {
+    match url.scheme() {
+        "https" => true,
+        "http" => match url.host() {
+            Some(Host::Domain(d)) => d == "localhost",
+            Some(Host::Ipv4(addr)) => addr.is_loopback(),
+            Some(Host::Ipv6(addr)) => addr.is_loopback(),
+            None => false,
+        },
+        scheme => native && scheme.contains('.'),
+    }
+}
+
+/// The response to a successful client registration (RFC 7591 section 3.2.1)
+#[derive(Clone, Debug)]
+pub struct ClientRegistration {
+    /// The newly issued client identifier
+    pub client_id: ClientId,
+    /// The newly issued client secret, if the client is confidential
+    pub client_secret: Option<String>,
+    /// Time at which the client identifier was issued, in seconds since the epoch
+    pub client_id_issued_at: Option<u64>,
+    /// Time at which the client secret will expire, in seconds since the epoch, or 0 if
+    /// it will not expire.  Required if `client_secret` is issued.
+    pub client_secret_expires_at: Option<u64>,
+    /// Access token the client uses at its client configuration endpoint (RFC 7592),
+    /// if that endpoint is offered
+    pub registration_access_token: Option<String>,
+    /// URL of the client configuration endpoint for this client (RFC 7592)
+    pub registration_client_uri: Option<String>,
+    /// The client metadata as registered, including any defaults filled in by the
+    /// Authorization Server
+    pub metadata: ClientMetadata,
+}
+
+impl ClientRegistration {
+    /// Serialize as a JSON object, leaving out the fields which are None
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"client_id\": {}",
+                                   ::serde_json::to_string(&*self.client_id).unwrap());
+        push_json_string(&mut json_str, "client_secret", &self.client_secret);
+        push_json_number(&mut json_str, "client_id_issued_at", &self.client_id_issued_at);
+        push_json_number(&mut json_str, "client_secret_expires_at",
+                         &self.client_secret_expires_at);
+        push_json_string(&mut json_str, "registration_access_token",
+                         &self.registration_access_token);
+        push_json_string(&mut json_str, "registration_client_uri",
+                         &self.registration_client_uri);
+        self.metadata.push_json(&mut json_str);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RegistrationErrorCode {
+    #[serde(rename="invalid_redirect_uri")]
+    InvalidRedirectUri,
+    #[serde(rename="invalid_client_metadata")]
+    InvalidClientMetadata,
+    #[serde(rename="invalid_software_statement")]
+    InvalidSoftwareStatement,
+    #[serde(rename="unapproved_software_statement")]
+    UnapprovedSoftwareStatement,
+}
+
+/// The response to a failed client registration (RFC 7591 section 3.2.2)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegistrationError {
+    pub error: RegistrationErrorCode,
+    pub error_description: Option<String>,
+}
+
+impl RegistrationError {
+    /// Serialize as a JSON object, leaving out the fields which are None
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"error\": {}",
+                                   ::serde_json::to_string(&self.error).unwrap());
+        push_json_string(&mut json_str, "error_description", &self.error_description);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
+
+fn metadata_error(description: &str) -> RegistrationError {
+    RegistrationError {
+        error: RegistrationErrorCode::InvalidClientMetadata,
+        error_description: Some(description.to_owned()),
+    }
+}
+
+#[test]
+fn test_client_metadata_validated() {
+    let metadata = ClientMetadata {
+        redirect_uris: Some(vec!["https://client.example.org/callback".to_owned()]),
+        .. ClientMetadata::default()
+    };
+    let metadata = metadata.validated().unwrap();
+    assert_eq!(metadata.token_endpoint_auth_method, Some("client_secret_basic".to_owned()));
+    assert_eq!(metadata.grant_types, Some(vec!["authorization_code".to_owned()]));
+    assert_eq!(metadata.client_type(), ClientType::ConfidentialClient);
+    assert_eq!(metadata.authn_method(), ClientAuthnMethod::ClientSecretBasic);
+
+    let metadata = ClientMetadata {
+        redirect_uris: Some(vec!["https://client.example.org/callback".to_owned()]),
+        token_endpoint_auth_method: Some("client_secret_post".to_owned()),
+        .. ClientMetadata::default()
+    };
+    let metadata = metadata.validated().unwrap();
+    assert_eq!(metadata.authn_method(), ClientAuthnMethod::ClientSecretPost);
+    assert_eq!(metadata.client_type(), ClientType::ConfidentialClient);
+
+    let metadata = ClientMetadata {
+        redirect_uris: Some(vec!["https://client.example.org/callback#frag".to_owned()]),
+        .. ClientMetadata::default()
+    };
+    assert_eq!(metadata.validated().unwrap_err().error,
+               RegistrationErrorCode::InvalidRedirectUri);
+
+    let rejected = ["javascript:alert(1)", "data:text/html,hi", "http://client.example.org/cb",
+                    "com.example.app:/callback"];
+    for uri in rejected.iter() {
+        let metadata = ClientMetadata {
+            redirect_uris: Some(vec![(*uri).to_owned()]),
+            .. ClientMetadata::default()
+        };
+        assert_eq!(metadata.validated().unwrap_err().error,
+                   RegistrationErrorCode::InvalidRedirectUri);
+    }
+
+    // Native clients may use loopback http and private-use schemes
+    let metadata = ClientMetadata {
+        redirect_uris: Some(vec!["http://127.0.0.1:51004/cb".to_owned(),
+                                 "com.example.app:/callback".to_owned()]),
+        token_endpoint_auth_method: Some("none".to_owned()),
+        .. ClientMetadata::default()
+    };
+    assert!(metadata.validated().is_ok());
+    let metadata = ClientMetadata {
+        redirect_uris: Some(vec!["javascript:alert(1)".to_owned()]),
+        token_endpoint_auth_method: Some("none".to_owned()),
+        .. ClientMetadata::default()
+    };
+    assert!(metadata.validated().is_err());
+
+    let metadata = ClientMetadata::default();
+    assert_eq!(metadata.validated().unwrap_err().error,
+               RegistrationErrorCode::InvalidRedirectUri);
+
+    let metadata = ClientMetadata {
+        token_endpoint_auth_method: Some("none".to_owned()),
+        grant_types: Some(vec!["client_credentials".to_owned()]),
+        response_types: Some(vec![]),
+        .. ClientMetadata::default()
+    };
+    assert_eq!(metadata.validated().unwrap_err().error,
+               RegistrationErrorCode::InvalidClientMetadata);
+
+    let metadata = ClientMetadata {
+        token_endpoint_auth_method: Some("private_key_jwt".to_owned()),
+        grant_types: Some(vec!["client_credentials".to_owned()]),
+        response_types: Some(vec![]),
+        .. ClientMetadata::default()
+    };
+    assert_eq!(metadata.validated().unwrap_err().error,
+               RegistrationErrorCode::InvalidClientMetadata);
+}
@@ -0,0 +1,94 @@
+
+use std::fmt;
+use std::fmt::Display;
+use openssl::crypto::hash::{hash, Type};
+use openssl::crypto::memcmp;
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+
+/// Code Challenge Method, either 'plain' or 'S256'.
+///
+/// See RFC 7636 Section 4.2.  Clients SHOULD use 'S256'.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CodeChallengeMethod {
+    /// The code challenge is the code verifier itself
+    Plain,
+    /// The code challenge is the base64url encoded SHA-256 hash of the code verifier
+    S256,
+}
+
+impl CodeChallengeMethod {
+    /// Parse the `code_challenge_method` parameter, returning None if the method is
+    /// not supported.
+    pub fn from_str(method: &str) -> Option<CodeChallengeMethod> {
+        match method {
+            "plain" => Some(CodeChallengeMethod::Plain),
+            "S256" => Some(CodeChallengeMethod::S256),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CodeChallengeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            CodeChallengeMethod::Plain => write!(f, "plain"),
+            CodeChallengeMethod::S256 => write!(f, "S256"),
+        }
+    }
+}
+
+/// A Proof Key for Code Exchange (PKCE) code challenge, as sent by the client in the
+/// authorization request.  See RFC 7636.
+///
+/// The Authorization Server must store this with the authorization code it issues,
+/// and return it from `AuthzServer::retrieve_client_authorization()`, so that the
+/// code verifier can be checked at the token endpoint.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CodeChallenge {
+    /// code_challenge as supplied in the request
+    pub challenge: String,
+
+    /// code_challenge_method as supplied in the request (defaults to `Plain`)
+    pub method: CodeChallengeMethod,
+}
+
+impl CodeChallenge {
+    /// Derive the code challenge from a code verifier (RFC 7636 section 4.2)
+    pub fn from_verifier(code_verifier: &str, method: CodeChallengeMethod) -> CodeChallenge {
+        let challenge = match method {
+            CodeChallengeMethod::Plain => code_verifier.to_owned(),
+            CodeChallengeMethod::S256 =>
+                hash(Type::SHA256, code_verifier.as_bytes()).to_base64(URL_SAFE),
+        };
+        CodeChallenge {
+            challenge: challenge,
+            method: method,
+        }
+    }
+
+    /// Verify that a code verifier matches this code challenge (RFC 7636 section 4.6)
+    pub fn verify(&self, code_verifier: &str) -> bool {
+        let derived = CodeChallenge::from_verifier(code_verifier, self.method);
+        derived.challenge.len() == self.challenge.len() &&
+            memcmp::eq(derived.challenge.as_bytes(), self.challenge.as_bytes())
+    }
+}
+
+#[test]
+fn test_code_challenge_s256() {
+    // Example from RFC 7636 Appendix B
+    let challenge = CodeChallenge::from_verifier(
+        "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk", CodeChallengeMethod::S256);
+    assert_eq!(&*challenge.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    assert!(challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+    assert!(! challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXj"));
+}
+
+#[test]
+fn test_code_challenge_plain() {
+    let challenge = CodeChallenge::from_verifier(
+        "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk", CodeChallengeMethod::Plain);
+    assert!(challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+    assert!(! challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjX"));
+}
@@ -0,0 +1,98 @@
+
+use openssl::crypto::rand::rand_bytes;
+use json::{push_json_string, push_json_number};
+use TokenData;
+
+/// The grant_type value for exchanging a device code at the token endpoint
+pub const DEVICE_CODE_GRANT_TYPE: &'static str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Lifetime of device codes and user codes, in seconds
+pub const DEVICE_CODE_LIFETIME: u64 = 600;
+
+/// Minimum time between polls of the token endpoint by the device, in seconds
+pub const DEVICE_POLL_INTERVAL: u64 = 5;
+
+/// Characters used in user codes: upper case consonants, which are easy to type and
+/// unlikely to spell anything (RFC 8628 section 6.1)
+const USER_CODE_CHARS: &'static [u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+/// The response from the device authorization endpoint (RFC 8628 section 3.2).  The
+/// device displays `user_code` and `verification_uri` to the user, and then polls the
+/// token endpoint with `device_code`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// The verification URI with the user code already filled in, e.g. for a QR code
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    /// Minimum time between polls of the token endpoint, in seconds.  Defaults to 5.
+    pub interval: Option<u64>,
+}
+
+impl DeviceAuthorization {
+    /// Serialize as the JSON device authorization response, leaving out
+    /// `verification_uri_complete` and `interval` if they are None.  Serde deserializes
+    /// missing fields as None, so it can parse the result.
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"device_code\": {},\r\n  \"user_code\": {},\
+                                    \r\n  \"verification_uri\": {}",
+                                   ::serde_json::to_string(&self.device_code).unwrap(),
+                                   ::serde_json::to_string(&self.user_code).unwrap(),
+                                   ::serde_json::to_string(&self.verification_uri).unwrap());
+        push_json_string(&mut json_str, "verification_uri_complete",
+                         &self.verification_uri_complete);
+        push_json_number(&mut json_str, "expires_in", &Some(self.expires_in));
+        push_json_number(&mut json_str, "interval", &self.interval);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
+
+/// The state of a device authorization, as reported by
+/// `AuthzServer::poll_device_authorization()` when the device polls the token endpoint
+/// (RFC 8628 section 3.5).
+#[derive(Debug)]
+pub enum DevicePollResult {
+    /// The user has not yet approved or denied the request
+    Pending,
+    /// The device is polling too often, and must wait 5 seconds longer between polls
+    SlowDown,
+    /// The user denied the request
+    Denied,
+    /// The device code has expired
+    Expired,
+    /// The user approved the request, and this token was issued
+    Granted(TokenData),
+}
+
+/// Generate a user code of 8 characters from a 20 character set (about 34 bits of
+/// entropy), formatted like "WDJB-MJHT" for readability (RFC 8628 section 6.1).
+pub fn generate_user_code() -> String {
+    let mut code = String::new();
+    while code.len() < 9 {
+        for b in rand_bytes(16) {
+            // Skip bytes that would bias the choice of character
+            if (b as usize) >= 256 - (256 % USER_CODE_CHARS.len()) {
+                continue;
+            }
+            if code.len() == 4 {
+                code.push('-');
+            }
+            code.push(USER_CODE_CHARS[b as usize % USER_CODE_CHARS.len()] as char);
+            if code.len() == 9 {
+                break;
+            }
+        }
+    }
+    code
+}
+
+#[test]
+fn test_generate_user_code() {
+    let code = generate_user_code();
+    assert_eq!(code.len(), 9);
+    assert_eq!(code.chars().nth(4), Some('-'));
+    assert!(code.bytes().enumerate().all(|(i, b)| i == 4 || USER_CODE_CHARS.contains(&b)));
+}
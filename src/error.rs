@@ -15,16 +15,37 @@ pub enum OAuthError {
     Url(::url::ParseError),
     Io(IoError),
     ParseInt(ParseIntError),
+    Hyper(::hyper::Error),
+    Json(::serde_json::Error),
     AuthzBadRequest,
     AuthzMissingClientId,
     AuthzUnknownClient,
     AuthzRedirectUrlNotRegistered,
     AuthzGrantTypeMissing,
+    AuthzGrantTypeUnsupported,
+    AuthzTokenTypeUnsupported,
+    AuthzRegistrationRefused,
+    AuthzIdTokenUnsupported,
     AuthzClientIdMismatch,
     AuthzGrantNotFound,
+    AuthzRequestUriInvalid,
+    AuthzPushedRequestUnsupported,
+    AuthzRequestObjectInvalid,
+    AuthzTargetInvalid,
+    AuthzUnknownUser,
+    AuthzBindingMessageInvalid,
     ClientCodeMissing,
     ClientStateMissing,
     ClientNonceMismatch,
+    ClientIssuerMismatch,
+    ClientEndpointMissing,
+    ClientKeyMissing,
+    ClientAuthnMethodMismatch,
+    JwtMalformed,
+    JwtSignatureInvalid,
+    JwtClaimsInvalid,
+    JwtExpired,
+    JwkInvalid,
     UnexpectedStatusCode,
 }
 
@@ -37,6 +58,8 @@ impl fmt::Display for OAuthError {
             OAuthError::Url(ref e) => e.fmt(f),
             OAuthError::Io(ref e) => e.fmt(f),
             OAuthError::ParseInt(ref e) => e.fmt(f),
+            OAuthError::Hyper(ref e) => e.fmt(f),
+            OAuthError::Json(ref e) => e.fmt(f),
             ref e => write!(f, "{}", e.description()),
         }
     }
@@ -50,16 +73,37 @@ impl StdError for OAuthError {
             OAuthError::Url(ref e) => e.description(),
             OAuthError::Io(ref e) => e.description(),
             OAuthError::ParseInt(ref e) => e.description(),
+            OAuthError::Hyper(ref e) => e.description(),
+            OAuthError::Json(ref e) => e.description(),
             OAuthError::AuthzBadRequest => "Bad Request",
             OAuthError::AuthzMissingClientId => "Missing `client_id`",
             OAuthError::AuthzUnknownClient => "Unknown Client",
             OAuthError::AuthzRedirectUrlNotRegistered => "`redirect_url` Not Registered",
             OAuthError::AuthzGrantTypeMissing => "`grant_type` Missing",
+            OAuthError::AuthzGrantTypeUnsupported => "`grant_type` Not Supported",
+            OAuthError::AuthzTokenTypeUnsupported => "Token Type Not Supported",
+            OAuthError::AuthzRegistrationRefused => "Client Registration Refused",
+            OAuthError::AuthzIdTokenUnsupported => "ID Tokens Not Supported",
             OAuthError::AuthzClientIdMismatch => "`client_id` mismatch",
             OAuthError::AuthzGrantNotFound => "grant not found",
+            OAuthError::AuthzRequestUriInvalid => "Invalid `request_uri`",
+            OAuthError::AuthzPushedRequestUnsupported => "Pushed Requests Not Supported",
+            OAuthError::AuthzRequestObjectInvalid => "Invalid Request Object",
+            OAuthError::AuthzTargetInvalid => "Invalid `audience` or `resource`",
+            OAuthError::AuthzUnknownUser => "Unknown User",
+            OAuthError::AuthzBindingMessageInvalid => "Invalid `binding_message`",
             OAuthError::ClientCodeMissing => "`code` Missing",
             OAuthError::ClientStateMissing => "`state` Missing",
             OAuthError::ClientNonceMismatch => "`nonce` Mismatch",
+            OAuthError::ClientIssuerMismatch => "`issuer` Mismatch",
+            OAuthError::ClientEndpointMissing => "Endpoint Not Published",
+            OAuthError::ClientKeyMissing => "Client Signing Key Missing",
+            OAuthError::ClientAuthnMethodMismatch => "Wrong Client Authentication Method",
+            OAuthError::JwtMalformed => "Malformed JWT",
+            OAuthError::JwtSignatureInvalid => "Invalid JWT Signature",
+            OAuthError::JwtClaimsInvalid => "Invalid JWT Claims",
+            OAuthError::JwtExpired => "JWT Expired",
+            OAuthError::JwkInvalid => "Invalid JWK",
             OAuthError::UnexpectedStatusCode => "Unexpected HTTP Status Code",
         }
     }
@@ -71,6 +115,8 @@ impl StdError for OAuthError {
             OAuthError::Url(ref e) => Some(e),
             OAuthError::Io(ref e) => Some(e),
             OAuthError::ParseInt(ref e) => Some(e),
+            OAuthError::Hyper(ref e) => Some(e),
+            OAuthError::Json(ref e) => Some(e),
             _ => None,
         }
     }
@@ -105,3 +151,15 @@ impl From<ParseIntError> for OAuthError {
         OAuthError::ParseInt(e)
     }
 }
+
+impl From<::hyper::Error> for OAuthError {
+    fn from(e: ::hyper::Error) -> OAuthError {
+        OAuthError::Hyper(e)
+    }
+}
+
+impl From<::serde_json::Error> for OAuthError {
+    fn from(e: ::serde_json::Error) -> OAuthError {
+        OAuthError::Json(e)
+    }
+}
@@ -0,0 +1,99 @@
+
+use std::collections::BTreeMap;
+use serde_json::Value;
+use jwt::Jwt;
+use ClientId;
+
+/// Claims about the authentication of an end-user, from which an ID token is built
+/// (OpenID Connect Core section 2).  These are supplied by
+/// `AuthzServer::get_id_token_claims()`.
+#[derive(Clone, Debug, Default)]
+pub struct IdTokenClaims {
+    /// Subject identifier: a locally unique and never reassigned identifier for the
+    /// end-user
+    pub sub: String,
+
+    /// Time at which the end-user authenticated, in seconds since the epoch.  This is
+    /// required if the authorization request included `max_age`.
+    pub auth_time: Option<u64>,
+
+    /// Authentication context class reference
+    pub acr: Option<String>,
+
+    /// Authentication methods references
+    pub amr: Option<Vec<String>>,
+
+    /// Any further claims about the end-user, e.g. `name` or `email`.  These cannot
+    /// replace the claims set above or by the Authorization Server.
+    pub claims: BTreeMap<String, Value>,
+}
+
+impl IdTokenClaims {
+    /// Whether these claims can answer an authorization request with the given
+    /// `max_age`, which requires the `auth_time` claim (OpenID Connect Core section 2)
+    pub fn satisfies_max_age(&self, max_age: Option<u64>) -> bool {
+        max_age.is_none() || self.auth_time.is_some()
+    }
+
+    /// Build the (unsigned) ID token for these claims, issued by `issuer` to
+    /// `client_id`, echoing the `nonce` from the authorization request.
+    pub fn to_jwt(&self, issuer: &str, client_id: &ClientId, nonce: Option<&str>,
+                  lifetime: u64) -> Jwt
+    {
+        let now = ::time::get_time().sec as u64;
+        let mut jwt = Jwt::new();
+        jwt.claims = self.claims.clone();
+        jwt.claims.insert("iss".to_owned(), Value::String(issuer.to_owned()));
+        jwt.claims.insert("sub".to_owned(), Value::String(self.sub.clone()));
+        jwt.claims.insert("aud".to_owned(), Value::String((**client_id).clone()));
+        jwt.claims.insert("exp".to_owned(), Value::U64(now + lifetime));
+        jwt.claims.insert("iat".to_owned(), Value::U64(now));
+        if let Some(auth_time) = self.auth_time {
+            jwt.claims.insert("auth_time".to_owned(), Value::U64(auth_time));
+        }
+        if let Some(nonce) = nonce {
+            jwt.claims.insert("nonce".to_owned(), Value::String(nonce.to_owned()));
+        }
+        if let Some(ref acr) = self.acr {
+            jwt.claims.insert("acr".to_owned(), Value::String(acr.clone()));
+        }
+        if let Some(ref amr) = self.amr {
+            jwt.claims.insert("amr".to_owned(), Value::Array(
+                amr.iter().map(|m| Value::String(m.clone())).collect()));
+        }
+        jwt
+    }
+}
+
+#[test]
+fn test_id_token_claims_to_jwt() {
+    let mut claims = IdTokenClaims {
+        sub: "24400320".to_owned(),
+        auth_time: Some(1311280969),
+        .. IdTokenClaims::default()
+    };
+    claims.claims.insert("sub".to_owned(), Value::String("someone-else".to_owned()));
+    claims.claims.insert("name".to_owned(), Value::String("Jane Doe".to_owned()));
+
+    let jwt = claims.to_jwt("https://server.example.com", &ClientId("s6BhdRkqt3".to_owned()),
+                            Some("n-0S6_WzA2Mj"), 600);
+    assert_eq!(jwt.claim_str("iss"), Some("https://server.example.com"));
+    assert_eq!(jwt.claim_str("sub"), Some("24400320"));
+    assert_eq!(jwt.claim_str("aud"), Some("s6BhdRkqt3"));
+    assert_eq!(jwt.claim_str("nonce"), Some("n-0S6_WzA2Mj"));
+    assert_eq!(jwt.claim_str("name"), Some("Jane Doe"));
+    assert_eq!(jwt.claim_u64("auth_time"), Some(1311280969));
+    assert_eq!(jwt.claim_u64("exp").unwrap() - jwt.claim_u64("iat").unwrap(), 600);
+}
+
+#[test]
+fn test_id_token_claims_satisfies_max_age() {
+    let mut claims = IdTokenClaims {
+        sub: "24400320".to_owned(),
+        .. IdTokenClaims::default()
+    };
+    assert!(claims.satisfies_max_age(None));
+    assert!(! claims.satisfies_max_age(Some(3600)));
+    claims.auth_time = Some(1311280969);
+    assert!(claims.satisfies_max_age(Some(3600)));
+}
@@ -0,0 +1,31 @@
+//! Helpers for hand-building JSON which skips fields that are None.
+
+/// Append `"name": value` to a JSON object under construction, if value is Some
+pub fn push_json_string(json_str: &mut String, name: &str, value: &Option<String>) {
+    if let Some(ref v) = *value {
+        json_str.push_str( &*format!(",\r\n  \"{}\": {}", name,
+                                     ::serde_json::to_string(v).unwrap()) );
+    }
+}
+
+/// Append `"name": [values]` to a JSON object under construction, if value is Some
+pub fn push_json_strings(json_str: &mut String, name: &str, value: &Option<Vec<String>>) {
+    if let Some(ref v) = *value {
+        json_str.push_str( &*format!(",\r\n  \"{}\": {}", name,
+                                     ::serde_json::to_string(v).unwrap()) );
+    }
+}
+
+/// Append `"name": number` to a JSON object under construction, if value is Some
+pub fn push_json_number(json_str: &mut String, name: &str, value: &Option<u64>) {
+    if let Some(v) = *value {
+        json_str.push_str( &*format!(",\r\n  \"{}\": {}", name, v) );
+    }
+}
+
+/// Append `"name": true|false` to a JSON object under construction, if value is Some
+pub fn push_json_bool(json_str: &mut String, name: &str, value: &Option<bool>) {
+    if let Some(v) = *value {
+        json_str.push_str( &*format!(",\r\n  \"{}\": {}", name, v) );
+    }
+}
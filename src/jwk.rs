@@ -0,0 +1,113 @@
+
+use json::push_json_string;
+use jwt::JwsKey;
+
+/// A JSON Web Key (RFC 7517), as published in a JSON Web Key Set so that others can
+/// verify the signatures made with the corresponding private key.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Jwk {
+    /// Key type, e.g. "RSA" or "EC".  Required.
+    pub kty: String,
+    /// Intended use of the key, "sig" for signatures
+    #[serde(rename="use")]
+    pub key_use: Option<String>,
+    /// Algorithm the key is used with, e.g. "RS256"
+    pub alg: Option<String>,
+    /// Key identifier, matching the `kid` header of JWTs signed with the key
+    pub kid: Option<String>,
+    /// RSA modulus, base64url encoded
+    pub n: Option<String>,
+    /// RSA public exponent, base64url encoded
+    pub e: Option<String>,
+    /// Elliptic curve, e.g. "P-256"
+    pub crv: Option<String>,
+    /// Elliptic curve x coordinate, base64url encoded
+    pub x: Option<String>,
+    /// Elliptic curve y coordinate, base64url encoded
+    pub y: Option<String>,
+}
+
+impl Jwk {
+    /// Serialize as a JSON object, leaving out the fields which are None, as RFC 7517
+    /// expects.  Serde deserializes missing fields as None, so it can parse the result.
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"kty\": {}",
+                                   ::serde_json::to_string(&self.kty).unwrap());
+        push_json_string(&mut json_str, "use", &self.key_use);
+        push_json_string(&mut json_str, "alg", &self.alg);
+        push_json_string(&mut json_str, "kid", &self.kid);
+        push_json_string(&mut json_str, "n", &self.n);
+        push_json_string(&mut json_str, "e", &self.e);
+        push_json_string(&mut json_str, "crv", &self.crv);
+        push_json_string(&mut json_str, "x", &self.x);
+        push_json_string(&mut json_str, "y", &self.y);
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
+
+/// Serialize a JSON Web Key Set (RFC 7517 section 5)
+pub fn jwk_set_json(keys: &[Jwk]) -> String {
+    let keys: Vec<String> = keys.iter().map(|k| k.as_json()).collect();
+    format!("{{\r\n  \"keys\": [{}]\r\n}}", keys.join(", "))
+}
+
+/// A set of signing keys, such as an Authorization Server's.  During key rotation this
+/// should include the retired keys as long as tokens signed with them remain valid.
+pub trait KeySet {
+    /// All the keys in the set
+    fn keys(&self) -> Vec<&JwsKey>;
+
+    /// Find the key to verify a JWT with, by its `kid` header.  If the JWT has no `kid`,
+    /// this only succeeds if there is just one key in the set.
+    fn find_key(&self, key_id: Option<&str>) -> Option<&JwsKey> {
+        let keys = self.keys();
+        match key_id {
+            Some(kid) => keys.into_iter().find(|k| k.key_id() == Some(kid)),
+            None if keys.len() == 1 => keys.into_iter().next(),
+            None => None,
+        }
+    }
+
+    /// The public keys of the set, for publishing as a JSON Web Key Set.  Keys which
+    /// cannot be published (e.g. shared secrets) are skipped.
+    fn public_jwks(&self) -> Vec<Jwk> {
+        self.keys().into_iter().filter_map(|k| k.public_jwk()).collect()
+    }
+}
+
+impl<K: JwsKey> KeySet for Vec<K> {
+    fn keys(&self) -> Vec<&JwsKey> {
+        self.iter().map(|k| k as &JwsKey).collect()
+    }
+}
+
+#[test]
+fn test_key_set_find_key() {
+    use jwt::HmacKey;
+
+    let keys = vec![
+        HmacKey { key: b"first".to_vec(), key_id: Some("1".to_owned()) },
+        HmacKey { key: b"second".to_vec(), key_id: Some("2".to_owned()) },
+    ];
+    assert_eq!(keys.find_key(Some("2")).and_then(|k| k.key_id()), Some("2"));
+    assert!(keys.find_key(Some("3")).is_none());
+    assert!(keys.find_key(None).is_none());
+
+    // Shared secrets are never published
+    assert!(keys.public_jwks().is_empty());
+}
+
+#[test]
+fn test_jwk_set_json() {
+    let jwk = Jwk {
+        kty: "RSA".to_owned(),
+        key_use: Some("sig".to_owned()),
+        kid: Some("1".to_owned()),
+        e: Some("AQAB".to_owned()),
+        .. Jwk::default()
+    };
+    assert_eq!(&*jwk_set_json(&[jwk]),
+               "{\r\n  \"keys\": [{\r\n  \"kty\": \"RSA\",\r\n  \"use\": \"sig\",\
+                \r\n  \"kid\": \"1\",\r\n  \"e\": \"AQAB\"\r\n}]\r\n}");
+}
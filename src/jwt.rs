@@ -0,0 +1,318 @@
+
+use std::collections::BTreeMap;
+use openssl::crypto::hash::{hash, Type};
+use openssl::crypto::hmac::hmac;
+use openssl::crypto::memcmp;
+use openssl::crypto::pkey::PKey;
+use openssl::crypto::rsa::RSA;
+use openssl::bn::BigNum;
+use rustc_serialize::base64::{FromBase64, ToBase64, URL_SAFE};
+use serde_json::Value;
+use jwk::Jwk;
+use OAuthError;
+
+/// Allowance for clock skew when checking the `exp` and `nbf` claims, in seconds
+const CLOCK_SKEW: u64 = 60;
+
+/// A key which signs and verifies JSON Web Signatures (RFC 7515).
+///
+/// `HmacKey` (HS256) and `RsaKey` (RS256) are provided.  Implement this yourself to
+/// use keys held elsewhere (such as in an HSM).
+pub trait JwsKey {
+    /// The JWS `alg` header value for signatures made with this key, e.g. "RS256"
+    /// (RFC 7518 section 3.1)
+    fn algorithm(&self) -> &str;
+
+    /// The `kid` header value identifying this key, if any
+    fn key_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Sign the JWS signing input
+    fn sign(&self, input: &[u8]) -> Result<Vec<u8>, OAuthError>;
+
+    /// Verify a signature over the JWS signing input
+    fn verify(&self, input: &[u8], signature: &[u8]) -> bool;
+
+    /// The public key, for publishing in a JSON Web Key Set, or None if it must not be
+    /// published (e.g. a shared secret).  The default implementation returns None.
+    fn public_jwk(&self) -> Option<Jwk> {
+        None
+    }
+}
+
+/// A shared secret key, for HMAC using SHA-256 (HS256)
+pub struct HmacKey {
+    pub key: Vec<u8>,
+    pub key_id: Option<String>,
+}
+
+impl JwsKey for HmacKey {
+    fn algorithm(&self) -> &str {
+        "HS256"
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.key_id.as_ref().map(|s| &**s)
+    }
+
+    fn sign(&self, input: &[u8]) -> Result<Vec<u8>, OAuthError> {
+        Ok(hmac(Type::SHA256, &*self.key, input))
+    }
+
+    fn verify(&self, input: &[u8], signature: &[u8]) -> bool {
+        let expected = hmac(Type::SHA256, &*self.key, input);
+        expected.len() == signature.len() && memcmp::eq(&*expected, signature)
+    }
+}
+
+/// An RSA key, for RSASSA-PKCS1-v1_5 using SHA-256 (RS256).  A public key suffices
+/// for verification.
+pub struct RsaKey {
+    pub key: PKey,
+    pub key_id: Option<String>,
+}
+
+impl RsaKey {
+    /// Create a public key from an RSA JSON Web Key (RFC 7518 section 6.3), such as one
+    /// from a client's JSON Web Key Set, for verifying signatures.
+    pub fn from_jwk(jwk: &Jwk) -> Result<RsaKey, OAuthError> {
+        let (n, e) = match (&*jwk.kty, jwk.n.as_ref(), jwk.e.as_ref()) {
+            ("RSA", Some(n), Some(e)) => (n, e),
+            _ => return Err(OAuthError::JwkInvalid),
+        };
+        let (n, e) = match (n.from_base64(), e.from_base64()) {
+            (Ok(n), Ok(e)) => (n, e),
+            _ => return Err(OAuthError::JwkInvalid),
+        };
+        let rsa = match (BigNum::new_from_slice(&*n), BigNum::new_from_slice(&*e)) {
+            (Ok(n), Ok(e)) => try!(RSA::from_public_components(n, e)
+                                   .map_err(|_| OAuthError::JwkInvalid)),
+            _ => return Err(OAuthError::JwkInvalid),
+        };
+        let mut key = PKey::new();
+        key.set_rsa(&rsa);
+        Ok(RsaKey {
+            key: key,
+            key_id: jwk.kid.clone(),
+        })
+    }
+}
+
+impl JwsKey for RsaKey {
+    fn algorithm(&self) -> &str {
+        "RS256"
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.key_id.as_ref().map(|s| &**s)
+    }
+
+    fn sign(&self, input: &[u8]) -> Result<Vec<u8>, OAuthError> {
+        Ok(self.key.sign_with_hash(&*hash(Type::SHA256, input), Type::SHA256))
+    }
+
+    fn verify(&self, input: &[u8], signature: &[u8]) -> bool {
+        self.key.verify_with_hash(&*hash(Type::SHA256, input), signature, Type::SHA256)
+    }
+
+    fn public_jwk(&self) -> Option<Jwk> {
+        let rsa = self.key.get_rsa();
+        let (n, e) = match (rsa.n(), rsa.e()) {
+            (Ok(n), Ok(e)) => (n, e),
+            _ => return None,
+        };
+        Some(Jwk {
+            kty: "RSA".to_owned(),
+            key_use: Some("sig".to_owned()),
+            alg: Some("RS256".to_owned()),
+            kid: self.key_id.clone(),
+            n: Some(n.to_vec().to_base64(URL_SAFE)),
+            e: Some(e.to_vec().to_base64(URL_SAFE)),
+            .. Jwk::default()
+        })
+    }
+}
+
+/// A JSON Web Token (RFC 7519), in JWS compact serialization
+#[derive(Clone, Debug, Default)]
+pub struct Jwt {
+    /// The JOSE header.  `alg` and `kid` are set from the key when signing.
+    pub header: BTreeMap<String, Value>,
+    /// The claims set
+    pub claims: BTreeMap<String, Value>,
+}
+
+impl Jwt {
+    pub fn new() -> Jwt {
+        Jwt::default()
+    }
+
+    /// Get a string claim
+    pub fn claim_str(&self, name: &str) -> Option<&str> {
+        self.claims.get(name).and_then(|v| v.as_string())
+    }
+
+    /// Get a numeric claim, such as `exp`
+    pub fn claim_u64(&self, name: &str) -> Option<u64> {
+        self.claims.get(name).and_then(|v| v.as_u64())
+    }
+
+    /// Check the `iss`, `aud`, `exp` and `nbf` claims (RFC 7519 section 4.1): the JWT
+    /// must have been issued by `issuer`, be intended for `audience` (amongst others,
+    /// if `aud` is an array), and be within its validity period.  `exp` is required.
+    pub fn check_claims(&self, issuer: &str, audience: &str) -> Result<(), OAuthError> {
+        if self.claim_str("iss") != Some(issuer) || ! self.has_audience(audience) ||
+            self.claim_u64("exp").is_none()
+        {
+            return Err(OAuthError::JwtClaimsInvalid);
+        }
+        self.check_validity()
+    }
+
+    /// Whether the `aud` claim is, or includes, `audience`
+    pub fn has_audience(&self, audience: &str) -> bool {
+        match self.claims.get("aud") {
+            Some(&Value::String(ref aud)) => aud == audience,
+            Some(&Value::Array(ref auds)) => auds.iter().any(|a| a.as_string() == Some(audience)),
+            _ => false,
+        }
+    }
+
+    /// Check that the JWT is within its validity period, as given by the `exp` and `nbf`
+    /// claims, if present
+    pub fn check_validity(&self) -> Result<(), OAuthError> {
+        let now = ::time::get_time().sec as u64;
+        if let Some(exp) = self.claim_u64("exp") {
+            if now >= exp.saturating_add(CLOCK_SKEW) {
+                return Err(OAuthError::JwtExpired);
+            }
+        }
+        if let Some(nbf) = self.claim_u64("nbf") {
+            if nbf > now.saturating_add(CLOCK_SKEW) {
+                return Err(OAuthError::JwtClaimsInvalid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sign this JWT with the given key, returning the compact serialization
+    pub fn sign(&self, key: &JwsKey) -> Result<String, OAuthError> {
+        let mut header = self.header.clone();
+        header.insert("alg".to_owned(), Value::String(key.algorithm().to_owned()));
+        if let Some(kid) = key.key_id() {
+            header.insert("kid".to_owned(), Value::String(kid.to_owned()));
+        }
+        let input = format!("{}.{}",
+                            try!(::serde_json::to_string(&header)).as_bytes()
+                                .to_base64(URL_SAFE),
+                            try!(::serde_json::to_string(&self.claims)).as_bytes()
+                                .to_base64(URL_SAFE));
+        let signature = try!(key.sign(input.as_bytes()));
+        Ok(format!("{}.{}", input, signature.to_base64(URL_SAFE)))
+    }
+
+    /// Decode a JWT and verify its signature with the given key.  The `alg` header must
+    /// match the key.  Claims such as `exp` and `aud` are not checked.
+    pub fn verify(token: &str, key: &JwsKey) -> Result<Jwt, OAuthError> {
+        let (jwt, input, signature) = try!(Jwt::split(token));
+        match jwt.header.get("alg").and_then(|v| v.as_string()) {
+            Some(alg) if alg == key.algorithm() => {},
+            _ => return Err(OAuthError::JwtSignatureInvalid),
+        }
+        if ! key.verify(input.as_bytes(), &*signature) {
+            return Err(OAuthError::JwtSignatureInvalid);
+        }
+        Ok(jwt)
+    }
+
+    /// Decode a JWT WITHOUT verifying its signature.  Only use this to determine which
+    /// key to verify it with (e.g. from its `iss` claim or `kid` header), and then
+    /// call `verify()`.
+    pub fn decode_unverified(token: &str) -> Result<Jwt, OAuthError> {
+        Jwt::split(token).map(|(jwt, _, _)| jwt)
+    }
+
+    fn split(token: &str) -> Result<(Jwt, &str, Vec<u8>), OAuthError> {
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 {
+            return Err(OAuthError::JwtMalformed);
+        }
+        let header = try!(decode_json_part(parts[0]));
+        let claims = try!(decode_json_part(parts[1]));
+        let signature = try!(parts[2].from_base64().map_err(|_| OAuthError::JwtMalformed));
+        let input = &token[..parts[0].len() + 1 + parts[1].len()];
+        Ok((Jwt { header: header, claims: claims }, input, signature))
+    }
+}
+
+fn decode_json_part(part: &str) -> Result<BTreeMap<String, Value>, OAuthError> {
+    let bytes = try!(part.from_base64().map_err(|_| OAuthError::JwtMalformed));
+    match ::serde_json::from_slice(&*bytes) {
+        Ok(Value::Object(map)) => Ok(map),
+        _ => Err(OAuthError::JwtMalformed),
+    }
+}
+
+#[test]
+fn test_jwt_verify_hs256() {
+    // Example from RFC 7515 Appendix A.1
+    let key = HmacKey {
+        key: "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-\
+              1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow".from_base64().unwrap(),
+        key_id: None,
+    };
+    let token = "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9.\
+                 eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFt\
+                 cGxlLmNvbS9pc19yb290Ijp0cnVlfQ.\
+                 dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    let jwt = Jwt::verify(token, &key).unwrap();
+    assert_eq!(jwt.claim_str("iss"), Some("joe"));
+    assert_eq!(jwt.claim_u64("exp"), Some(1300819380));
+
+    // Tampered claims must not verify
+    let tampered = token.replace("eyJpc3MiOiJqb2Ui", "eyJpc3MiOiJib2Ui");
+    assert!(Jwt::verify(&*tampered, &key).is_err());
+}
+
+#[test]
+fn test_jwt_sign_hs256() {
+    let key = HmacKey {
+        key: b"secret".to_vec(),
+        key_id: Some("1".to_owned()),
+    };
+    let mut jwt = Jwt::new();
+    jwt.claims.insert("sub".to_owned(), Value::String("alice".to_owned()));
+    let token = jwt.sign(&key).unwrap();
+
+    let verified = Jwt::verify(&*token, &key).unwrap();
+    assert_eq!(verified.claim_str("sub"), Some("alice"));
+    assert_eq!(verified.header.get("kid").and_then(|v| v.as_string()), Some("1"));
+
+    let other_key = HmacKey {
+        key: b"other".to_vec(),
+        key_id: None,
+    };
+    assert!(Jwt::verify(&*token, &other_key).is_err());
+}
+
+#[test]
+fn test_jwt_check_claims() {
+    let now = ::time::get_time().sec as u64;
+    let mut jwt = Jwt::new();
+    jwt.claims.insert("iss".to_owned(), Value::String("https://as.example.com".to_owned()));
+    jwt.claims.insert("aud".to_owned(), Value::Array(vec![
+        Value::String("https://rs1.example.com".to_owned()),
+        Value::String("https://rs2.example.com".to_owned())]));
+    jwt.claims.insert("exp".to_owned(), Value::U64(now + 300));
+    assert!(jwt.check_claims("https://as.example.com", "https://rs2.example.com").is_ok());
+    assert!(jwt.check_claims("https://as.example.com", "https://rs3.example.com").is_err());
+    assert!(jwt.check_claims("https://other.example.com", "https://rs1.example.com").is_err());
+
+    jwt.claims.insert("exp".to_owned(), Value::U64(now - 300));
+    assert!(jwt.check_claims("https://as.example.com", "https://rs1.example.com").is_err());
+
+    // Extreme values must not overflow
+    jwt.claims.insert("exp".to_owned(), Value::U64(::std::u64::MAX));
+    assert!(jwt.check_validity().is_ok());
+}
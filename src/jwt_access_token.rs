@@ -0,0 +1,204 @@
+
+use std::collections::BTreeMap;
+use serde_json::Value;
+use textnonce::TextNonce;
+use jwt::{Jwt, JwsKey};
+use jwk::KeySet;
+use {ClientId, TokenData, TokenIntrospection, OAuthError};
+
+/// The JWS `typ` header value of JWT access tokens (RFC 9068 section 2.1)
+const AT_JWT_TYPE: &'static str = "at+jwt";
+
+/// A JSON Web Token profile access token (RFC 9068), which resource servers can
+/// validate locally with the Authorization Server's public key, rather than asking
+/// the Authorization Server about each token.
+///
+/// This is an optional token format.  To issue one, create it with `new()` in your
+/// `AuthzServer::issue_token_to_client()` (or other token issuing functions), and
+/// return `to_token_data()`.  Resource servers can then call `verify()` from
+/// `ResourceServer::validate_access_token()`.
+///
+/// Tokens are signed with HS256 (`HmacKey`) or RS256 (`RsaKey`).
+#[derive(Clone, Debug)]
+pub struct JwtAccessToken {
+    /// Issuer identifier of the Authorization Server
+    pub iss: String,
+    /// Subject: the resource owner, or for the client credentials grant, the client
+    pub sub: String,
+    /// Audience: the resource server the token is intended for.  From `verify()`, this
+    /// is the audience the token was checked against, which may be one of several in
+    /// its `aud` claim.
+    pub aud: String,
+    /// Expiry time, in seconds since the epoch
+    pub exp: u64,
+    /// Issue time, in seconds since the epoch
+    pub iat: u64,
+    /// Unique identifier of the token
+    pub jti: String,
+    /// The client the token was issued to
+    pub client_id: ClientId,
+    /// Space-delimited scope granted
+    pub scope: Option<String>,
+    /// Time at which the resource owner authenticated, in seconds since the epoch
+    pub auth_time: Option<u64>,
+    /// Any further claims, e.g. `roles`.  These cannot replace the claims above.
+    pub claims: BTreeMap<String, Value>,
+}
+
+impl JwtAccessToken {
+    /// Create an access token valid for `lifetime` seconds from now, with a fresh
+    /// unique identifier.
+    pub fn new(issuer: &str, subject: &str, audience: &str, client_id: &ClientId,
+               scope: Option<&str>, lifetime: u64) -> JwtAccessToken
+    {
+        let now = ::time::get_time().sec as u64;
+        JwtAccessToken {
+            iss: issuer.to_owned(),
+            sub: subject.to_owned(),
+            aud: audience.to_owned(),
+            exp: now + lifetime,
+            iat: now,
+            jti: TextNonce::sized_urlsafe(32).unwrap().into_string(),
+            client_id: client_id.clone(),
+            scope: scope.map(|s| s.to_owned()),
+            auth_time: None,
+            claims: BTreeMap::new(),
+        }
+    }
+
+    /// Sign the access token, returning the JWT
+    pub fn sign(&self, key: &JwsKey) -> Result<String, OAuthError> {
+        let mut jwt = Jwt::new();
+        jwt.header.insert("typ".to_owned(), Value::String(AT_JWT_TYPE.to_owned()));
+        jwt.claims = self.claims.clone();
+        jwt.claims.insert("iss".to_owned(), Value::String(self.iss.clone()));
+        jwt.claims.insert("sub".to_owned(), Value::String(self.sub.clone()));
+        jwt.claims.insert("aud".to_owned(), Value::String(self.aud.clone()));
+        jwt.claims.insert("exp".to_owned(), Value::U64(self.exp));
+        jwt.claims.insert("iat".to_owned(), Value::U64(self.iat));
+        jwt.claims.insert("jti".to_owned(), Value::String(self.jti.clone()));
+        jwt.claims.insert("client_id".to_owned(), Value::String((*self.client_id).clone()));
+        if let Some(ref scope) = self.scope {
+            jwt.claims.insert("scope".to_owned(), Value::String(scope.clone()));
+        }
+        if let Some(auth_time) = self.auth_time {
+            jwt.claims.insert("auth_time".to_owned(), Value::U64(auth_time));
+        }
+        jwt.sign(key)
+    }
+
+    /// Sign the access token, and wrap it up as the response from the token endpoint.
+    /// Add a refresh token afterwards if you issue one.
+    pub fn to_token_data(&self, key: &JwsKey) -> Result<TokenData, OAuthError> {
+        Ok(TokenData {
+            access_token: try!(self.sign(key)),
+            token_type: "bearer".to_owned(),
+            expires_in: Some(self.exp.saturating_sub(self.iat) as u32),
+            refresh_token: None,
+            scope: self.scope.clone(),
+            id_token: None,
+            issued_token_type: None,
+        })
+    }
+
+    /// Verify a JWT access token as a resource server (RFC 9068 section 4): it must be
+    /// typed `at+jwt`, signed by a key in `key_set`, issued by `issuer`, intended for
+    /// `audience` (alone or among others), and unexpired.
+    pub fn verify(token: &str, key_set: &KeySet, issuer: &str, audience: &str)
+                  -> Result<JwtAccessToken, OAuthError>
+    {
+        let unverified = try!(Jwt::decode_unverified(token));
+        let key = match key_set.find_key(
+            unverified.header.get("kid").and_then(|v| v.as_string()))
+        {
+            Some(k) => k,
+            None => return Err(OAuthError::JwtSignatureInvalid),
+        };
+        let jwt = try!(Jwt::verify(token, key));
+
+        let typ = jwt.header.get("typ").and_then(|v| v.as_string())
+            .map(|t| t.to_lowercase());
+        match typ.as_ref().map(|t| &**t) {
+            Some("at+jwt") | Some("application/at+jwt") => {},
+            _ => return Err(OAuthError::JwtClaimsInvalid),
+        }
+        try!(jwt.check_claims(issuer, audience));
+
+        let (sub, exp, iat, jti, client_id) = match (jwt.claim_str("sub"),
+                                                     jwt.claim_u64("exp"),
+                                                     jwt.claim_u64("iat"),
+                                                     jwt.claim_str("jti"),
+                                                     jwt.claim_str("client_id")) {
+            (Some(sub), Some(exp), Some(iat), Some(jti), Some(client_id)) =>
+                (sub.to_owned(), exp, iat, jti.to_owned(), client_id.to_owned()),
+            _ => return Err(OAuthError::JwtClaimsInvalid),
+        };
+
+        let mut claims = jwt.claims.clone();
+        for name in &["iss", "sub", "aud", "exp", "iat", "jti", "client_id", "scope",
+                      "auth_time"] {
+            claims.remove(*name);
+        }
+
+        Ok(JwtAccessToken {
+            iss: issuer.to_owned(),
+            sub: sub,
+            aud: audience.to_owned(),
+            exp: exp,
+            iat: iat,
+            jti: jti,
+            client_id: ClientId(client_id),
+            scope: jwt.claim_str("scope").map(|s| s.to_owned()),
+            auth_time: jwt.claim_u64("auth_time"),
+            claims: claims,
+        })
+    }
+
+    /// The token's metadata, as returned from `ResourceServer::validate_access_token()`
+    pub fn to_introspection(&self) -> TokenIntrospection {
+        TokenIntrospection {
+            active: true,
+            scope: self.scope.clone(),
+            client_id: Some((*self.client_id).clone()),
+            token_type: Some("bearer".to_owned()),
+            exp: Some(self.exp),
+            iat: Some(self.iat),
+            sub: Some(self.sub.clone()),
+            aud: Some(self.aud.clone()),
+            iss: Some(self.iss.clone()),
+            jti: Some(self.jti.clone()),
+            .. TokenIntrospection::default()
+        }
+    }
+}
+
+#[test]
+fn test_jwt_access_token() {
+    use jwt::HmacKey;
+
+    let keys = vec![HmacKey { key: b"secret".to_vec(), key_id: Some("k1".to_owned()) }];
+    let token = JwtAccessToken::new("https://as.example.com", "alice",
+                                    "https://rs.example.com", &ClientId("c1".to_owned()),
+                                    Some("read"), 300);
+    let token_data = token.to_token_data(&keys[0]).unwrap();
+    assert_eq!(token_data.expires_in, Some(300));
+
+    let verified = JwtAccessToken::verify(&*token_data.access_token, &keys,
+                                          "https://as.example.com",
+                                          "https://rs.example.com").unwrap();
+    assert_eq!(&*verified.sub, "alice");
+    assert_eq!(verified.client_id, ClientId("c1".to_owned()));
+    assert_eq!(verified.scope, Some("read".to_owned()));
+    assert_eq!(verified.jti, token.jti);
+
+    assert!(JwtAccessToken::verify(&*token_data.access_token, &keys,
+                                   "https://as.example.com",
+                                   "https://other.example.com").is_err());
+
+    // An ID token (or any other JWT) must not be accepted as an access token
+    let mut jwt = Jwt::decode_unverified(&*token_data.access_token).unwrap();
+    jwt.header.remove("typ");
+    let other = jwt.sign(&keys[0]).unwrap();
+    assert!(JwtAccessToken::verify(&*other, &keys, "https://as.example.com",
+                                   "https://rs.example.com").is_err());
+}
@@ -0,0 +1,76 @@
+
+use serde_json::Value;
+use textnonce::TextNonce;
+use jwt::{Jwt, JwsKey};
+use jwk::KeySet;
+use OAuthError;
+
+/// The grant_type value for exchanging a JWT assertion at the token endpoint
+pub const JWT_BEARER_GRANT_TYPE: &'static str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Create a JWT assertion (RFC 7523 section 3), such as for a service account to obtain
+/// an access token with.  `audience` identifies the Authorization Server (its issuer
+/// identifier or token endpoint URL).  The assertion is valid for `lifetime` seconds,
+/// which should be short.
+pub fn sign_jwt_assertion(issuer: &str, subject: &str, audience: &str, lifetime: u64,
+                          key: &JwsKey) -> Result<String, OAuthError>
+{
+    let now = ::time::get_time().sec as u64;
+    let mut jwt = Jwt::new();
+    jwt.claims.insert("iss".to_owned(), Value::String(issuer.to_owned()));
+    jwt.claims.insert("sub".to_owned(), Value::String(subject.to_owned()));
+    jwt.claims.insert("aud".to_owned(), Value::String(audience.to_owned()));
+    jwt.claims.insert("iat".to_owned(), Value::U64(now));
+    jwt.claims.insert("exp".to_owned(), Value::U64(now + lifetime));
+    jwt.claims.insert("jti".to_owned(),
+                      Value::String(TextNonce::sized_urlsafe(32).unwrap().into_string()));
+    jwt.sign(key)
+}
+
+/// Verify a JWT assertion (RFC 7523 section 3) from `issuer`: it must be signed by a
+/// key in `key_set` (which should be the keys trusted for that issuer), have an `iss`
+/// claim of `issuer` and a `sub` claim, be intended for one of `audiences`, and be
+/// unexpired.  Use `Jwt::decode_unverified()` to find the issuer first.
+pub fn verify_jwt_assertion(assertion: &str, issuer: &str, key_set: &KeySet,
+                            audiences: &[&str]) -> Result<Jwt, OAuthError>
+{
+    let unverified = try!(Jwt::decode_unverified(assertion));
+    let key = match key_set.find_key(
+        unverified.header.get("kid").and_then(|v| v.as_string()))
+    {
+        Some(k) => k,
+        None => return Err(OAuthError::JwtSignatureInvalid),
+    };
+    let jwt = try!(Jwt::verify(assertion, key));
+
+    let audience = match audiences.iter().find(|a| jwt.has_audience(a)) {
+        Some(a) => a,
+        None => return Err(OAuthError::JwtClaimsInvalid),
+    };
+    try!(jwt.check_claims(issuer, audience));
+    if jwt.claim_str("sub").is_none() {
+        return Err(OAuthError::JwtClaimsInvalid);
+    }
+    Ok(jwt)
+}
+
+#[test]
+fn test_jwt_assertion() {
+    use jwt::HmacKey;
+
+    let keys = vec![HmacKey { key: b"secret".to_vec(), key_id: None }];
+    let assertion = sign_jwt_assertion("https://jwt-idp.example.com",
+                                       "mailto:mike@example.com",
+                                       "https://authz.example.net", 300, &keys[0]).unwrap();
+
+    let jwt = verify_jwt_assertion(&*assertion, "https://jwt-idp.example.com", &keys,
+                                   &["https://authz.example.net",
+                                     "https://authz.example.net/token"]).unwrap();
+    assert_eq!(jwt.claim_str("sub"), Some("mailto:mike@example.com"));
+    assert!(jwt.claim_str("jti").is_some());
+
+    assert!(verify_jwt_assertion(&*assertion, "https://other-idp.example.com", &keys,
+                                 &["https://authz.example.net"]).is_err());
+    assert!(verify_jwt_assertion(&*assertion, "https://jwt-idp.example.com", &keys,
+                                 &["https://other.example.net"]).is_err());
+}
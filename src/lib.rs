@@ -34,7 +34,9 @@ in order to get a working system.  These include:
 
 <ul>
 <li>Initial client registration (between the Client and the Authorization
-    Server).  Often people just use config files, but this is for you to decide.</li>
+    Server).  Often people just use config files, but this is for you to decide.
+    Dynamic client registration (RFC 7591) and client configuration management
+    (RFC 7592) are supported, but you must store the registered clients.</li>
 <li>Storing state.  Often database tables are used.  Manytimes the Authorization
     Server and Resource Server use the same database, or perhaps are the same
     server.  This is out of scope, and left up to you.</li>
@@ -56,15 +58,38 @@ allow.  That being said, the following limitations currently apply:
 
 <ul>
 <li>We only explicitly support the "authorization code" grant type, which is the most common
-    and most secure.  "Implicit", "resource owner password credentials", and "client
-    credentials" grant types are not supported.
-<li>The authorization server acts on behalf of the resource server, so virtually we are not
-    supporting independent resource servers.
+    and most secure, along with refreshing access tokens via refresh tokens, and the
+    "client credentials" grant type for confidential clients acting on their own behalf.
+    The "resource owner password credentials" and "implicit" grant types are supported,
+    but only for clients you explicitly allow them for.  The device authorization grant
+    (RFC 8628) is supported for devices which cannot receive redirects, and token
+    exchange (RFC 8693) for services which act on behalf of their callers.  Signed JWT
+    assertions (RFC 7523) may be exchanged for tokens, e.g. by service accounts, and so
+    may SAML 2.0 assertions (RFC 7522) if you validate them.  Client-Initiated
+    Backchannel Authentication (CIBA) is supported in poll, ping and push modes, for
+    clients which start authentication for users who approve it on their own devices.
+    Authorization requests may be pushed to the Authorization Server directly (RFC 9126),
+    and this may be required of particular clients.  So may signing authorization
+    requests as request objects (RFC 9101), which are verified with the client's keys.
+<li>Confidential clients authenticate with their client secret, either with HTTP Basic
+    authentication (`client_secret_basic`) or in the request body
+    (`client_secret_post`), or with JWT client assertions (RFC 7523 section 2.2) signed
+    with either the client secret (`client_secret_jwt`) or one of the client's
+    registered keys (`private_key_jwt`).  Public clients do not authenticate (`none`),
+    and identify themselves with the `client_id` parameter.  Each client must use the
+    method registered for it, which is also the one the `Client` trait uses.
+<li>The authorization server may act on behalf of the resource server.  Independent
+    resource servers are supported via the token introspection endpoint (RFC 7662) and
+    the `ResourceServer` trait, which accepts bearer tokens (RFC 6750).  Access tokens
+    may optionally be JWTs (RFC 9068), which resource servers can validate locally.
+<li>OpenID Connect login is supported: an ID token is issued along with the access token
+    when the `openid` scope is granted, if you supply a signing key and the claims.
+    The UserInfo endpoint, discovery metadata and JSON Web Key Set publishing are also
+    supported.
 <li>We do not enforce that traffic be protected via TLS, although the standard requires that
     most (and suggests all) traffic be so protected.  This is left up to the user.</li>
 <li>All IDs and tokens are taken to be respresented in UTF-8 encodings.  We will not
     work with other encodings.  The standard is silent on most encoding issues.</li>
-<li>Refresh tokens are not yet supported</li>
 <li>I'm not sure that the HTTP Status Codes returned to the user-agent on various failures
     are appropriate.</li>
 <li>FIXME: More limitations will be added to this list as the development progresses.</li>
@@ -78,28 +103,73 @@ extern crate url;
 #[macro_use] extern crate hyper;
 extern crate textnonce;
 extern crate serde_json;
+extern crate openssl;
+extern crate rustc_serialize;
+extern crate time;
 
 pub mod syntax;
+pub mod scope;
 pub mod authz_server;
+pub mod resource_server;
+pub mod authz_server_metadata;
 pub mod authz_request;
+pub mod request_object;
+pub mod client_authorization;
+pub mod code_challenge;
 pub mod authz_error;
 pub mod token_data;
 pub mod token_error;
+pub mod token_type_hint;
+pub mod token_introspection;
+pub mod token_exchange;
+pub mod id_token;
+pub mod userinfo;
+pub mod jwt;
+pub mod jwk;
+pub mod jwt_access_token;
+pub mod jwt_bearer;
+pub mod saml2_bearer;
+pub mod bearer_error;
 pub mod redirect_uri;
 pub mod client;
 pub mod client_id;
 pub mod client_type;
+pub mod client_authn_method;
+pub mod response_type;
 pub mod client_data;
+pub mod client_registration;
+pub mod device_authorization;
+pub mod backchannel_authentication;
 pub mod error;
+mod json;
 
 pub use authz_server::AuthzServer;
+pub use resource_server::ResourceServer;
+pub use authz_server_metadata::{AuthzServerMetadata, ProviderMetadata};
 pub use authz_request::AuthzRequest;
+pub use client_authorization::ClientAuthorization;
+pub use code_challenge::{CodeChallenge, CodeChallengeMethod};
 pub use authz_error::{AuthzError, AuthzErrorCode};
 pub use token_data::TokenData;
 pub use token_error::{TokenError, TokenErrorCode};
+pub use token_type_hint::TokenTypeHint;
+pub use token_introspection::TokenIntrospection;
+pub use token_exchange::{TokenType, TokenExchangeMode, TokenExchangeRequest};
+pub use id_token::IdTokenClaims;
+pub use jwt::{Jwt, JwsKey, HmacKey, RsaKey};
+pub use jwk::{Jwk, KeySet};
+pub use jwt_access_token::JwtAccessToken;
+pub use bearer_error::{BearerError, BearerErrorCode};
 pub use redirect_uri::RedirectUri;
 pub use client::Client;
 pub use client_id::ClientId;
 pub use client_type::ClientType;
+pub use client_authn_method::ClientAuthnMethod;
+pub use response_type::ResponseType;
 pub use client_data::ClientData;
+pub use client_registration::{ClientMetadata, ClientRegistration, RegistrationError,
+                              RegistrationErrorCode};
+pub use device_authorization::{DeviceAuthorization, DevicePollResult};
+pub use backchannel_authentication::{DeliveryMode, BackchannelAuthRequest,
+                                     BackchannelAuthResponse, BackchannelAuthResult};
 pub use error::OAuthError;
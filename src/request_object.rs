@@ -0,0 +1,123 @@
+
+use std::collections::HashMap;
+use serde_json::Value;
+use jwt::{Jwt, JwsKey};
+use jwk::KeySet;
+use {ClientId, OAuthError};
+
+/// The JWS `typ` header value of request objects (RFC 9101 section 10.8)
+const REQUEST_OBJECT_TYPE: &'static str = "oauth-authz-req+jwt";
+
+/// JWT claims of a request object which are not authorization request parameters
+const JWT_CLAIMS: &'static [&'static str] = &["iss", "aud", "exp", "iat", "nbf", "jti"];
+
+/// Sign authorization request parameters as a request object (RFC 9101 section 4), to
+/// send as the `request` parameter of an authorization request (or to push with it).
+/// `audience` is the Authorization Server's issuer identifier.  The request object is
+/// valid for `lifetime` seconds.
+pub fn sign_request_object(params: &[(&str, &str)], client_id: &ClientId, audience: &str,
+                           lifetime: u64, key: &JwsKey) -> Result<String, OAuthError>
+{
+    let now = ::time::get_time().sec as u64;
+    let mut jwt = Jwt::new();
+    jwt.header.insert("typ".to_owned(), Value::String(REQUEST_OBJECT_TYPE.to_owned()));
+    for &(name, value) in params {
+        jwt.claims.insert(name.to_owned(), Value::String(value.to_owned()));
+    }
+    jwt.claims.insert("client_id".to_owned(), Value::String((**client_id).clone()));
+    jwt.claims.insert("iss".to_owned(), Value::String((**client_id).clone()));
+    jwt.claims.insert("aud".to_owned(), Value::String(audience.to_owned()));
+    jwt.claims.insert("iat".to_owned(), Value::U64(now));
+    jwt.claims.insert("exp".to_owned(), Value::U64(now + lifetime));
+    jwt.sign(key)
+}
+
+/// Verify a request object sent by the client `client_id` (RFC 9101 section 6), and
+/// return the authorization request parameters it contains.  It must be signed by one
+/// of the client's keys, and its `client_id` claim must match.  If present, `iss` must
+/// be the client, `aud` must include `issuer`, and it must not have expired.  If
+/// `strict`, `exp` and `aud` must be present, and so must `issuer` to check `aud`
+/// against (RFC 9101 section 10.2 recommends these to limit replay).
+///
+/// Parameters which are not strings (such as `max_age`) are converted to strings, as
+/// if they had been sent in the query string.
+pub fn verify_request_object(request: &str, client_id: &ClientId, key_set: &KeySet,
+                             issuer: Option<&str>, strict: bool)
+                             -> Result<HashMap<String, String>, OAuthError>
+{
+    let unverified = try!(Jwt::decode_unverified(request));
+    let key = match key_set.find_key(
+        unverified.header.get("kid").and_then(|v| v.as_string()))
+    {
+        Some(k) => k,
+        None => return Err(OAuthError::JwtSignatureInvalid),
+    };
+    let jwt = try!(Jwt::verify(request, key));
+
+    if jwt.claim_str("client_id") != Some(&***client_id) {
+        return Err(OAuthError::AuthzClientIdMismatch);
+    }
+    if jwt.claims.contains_key("iss") && jwt.claim_str("iss") != Some(&***client_id) {
+        return Err(OAuthError::JwtClaimsInvalid);
+    }
+    if let Some(issuer) = issuer {
+        if jwt.claims.contains_key("aud") && ! jwt.has_audience(issuer) {
+            return Err(OAuthError::JwtClaimsInvalid);
+        }
+    }
+    if strict {
+        if jwt.claim_u64("exp").is_none() ||
+            ! issuer.map(|i| jwt.has_audience(i)).unwrap_or(false)
+        {
+            return Err(OAuthError::JwtClaimsInvalid);
+        }
+    }
+    try!(jwt.check_validity());
+
+    let mut params: HashMap<String, String> = HashMap::new();
+    for (name, value) in jwt.claims {
+        if JWT_CLAIMS.contains(&&*name) {
+            continue;
+        }
+        let value = match value {
+            Value::String(s) => s,
+            Value::U64(n) => format!("{}", n),
+            Value::I64(n) => format!("{}", n),
+            other => try!(::serde_json::to_string(&other)),
+        };
+        params.insert(name, value);
+    }
+    Ok(params)
+}
+
+#[test]
+fn test_request_object() {
+    use jwt::HmacKey;
+
+    let keys = vec![HmacKey { key: b"secret".to_vec(), key_id: None }];
+    let client_id = ClientId("s6BhdRkqt3".to_owned());
+    let request = sign_request_object(&[("response_type", "code"), ("state", "af0ifjsldkj")],
+                                      &client_id, "https://server.example.com", 60,
+                                      &keys[0]).unwrap();
+
+    let params = verify_request_object(&*request, &client_id, &keys,
+                                       Some("https://server.example.com"), true).unwrap();
+    assert_eq!(params.len(), 3);
+    assert_eq!(params.get("response_type").map(|s| &**s), Some("code"));
+    assert_eq!(params.get("client_id").map(|s| &**s), Some("s6BhdRkqt3"));
+
+    assert!(verify_request_object(&*request, &ClientId("other".to_owned()), &keys,
+                                  Some("https://server.example.com"), false).is_err());
+    assert!(verify_request_object(&*request, &client_id, &keys,
+                                  Some("https://other.example.com"), false).is_err());
+
+    // Without aud and exp, a request object is only accepted when not strict
+    let mut jwt = Jwt::new();
+    jwt.claims.insert("client_id".to_owned(), Value::String("s6BhdRkqt3".to_owned()));
+    jwt.claims.insert("response_type".to_owned(), Value::String("code".to_owned()));
+    let request = jwt.sign(&keys[0]).unwrap();
+    assert!(verify_request_object(&*request, &client_id, &keys,
+                                  Some("https://server.example.com"), false).is_ok());
+    assert!(verify_request_object(&*request, &client_id, &keys,
+                                  Some("https://server.example.com"), true).is_err());
+}
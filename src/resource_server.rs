@@ -0,0 +1,179 @@
+
+use std::io::Read;
+use hyper::server::{Request, Response};
+use hyper::uri::RequestUri;
+use hyper::method::Method;
+use hyper::header::{Authorization, Bearer, ContentType};
+use hyper::mime::{Mime, TopLevel, SubLevel};
+use url::Url;
+use authz_server::WwwAuthenticate;
+use scope::scope_is_subset;
+use syntax::valid_access_token_str;
+use {OAuthError, TokenIntrospection, BearerError, BearerErrorCode};
+
+pub trait ResourceServer<C>
+{
+    /// Validate an access token presented to this resource server, returning its
+    /// metadata.  How this is done is up to you: you might look it up in a database
+    /// shared with the Authorization Server, call the Authorization Server's
+    /// introspection endpoint (RFC 7662), or verify a self-contained token locally.
+    ///
+    /// Return a `TokenIntrospection` with `active` false if the token is unknown,
+    /// expired, revoked, or not intended for this resource server.  Return an error
+    /// only if the token could not be checked (e.g. the database is unavailable); the
+    /// request is then refused with 503 Service Unavailable.
+    ///
+    /// `context` comes from whatever you pass into `authorize_request()`
+    fn validate_access_token(&self, context: &mut C, access_token: &str)
+                             -> Result<TokenIntrospection, OAuthError>;
+
+    /// The realm to report in `WWW-Authenticate` challenges, if any.
+    fn get_realm(&self) -> Option<String> {
+        None
+    }
+
+    /// Authorize an HTTP request for a protected resource, which must present a bearer
+    /// access token (RFC 6750).  If `required_scope` is supplied, the access token
+    /// must have been granted every scope token in it.
+    ///
+    /// On success, the access token's metadata is returned, so that you can determine
+    /// whose resources may be accessed.  On failure, pass the error on to
+    /// `deny_request()`.
+    ///
+    /// If the access token is sent in the form-encoded body, the body is consumed.
+    fn authorize_request(&self, context: &mut C, request: &mut Request,
+                         required_scope: Option<&str>)
+                         -> Result<TokenIntrospection, BearerError>
+    {
+        let access_token = match try!(extract_bearer_token(request)) {
+            Some(t) => t,
+            None => return Err(BearerError {
+                error: None,
+                error_description: None,
+                error_uri: None,
+                scope: None,
+                unavailable: false,
+            }),
+        };
+
+        let introspection = match self.validate_access_token(context, &*access_token) {
+            Ok(ref i) if i.active => i.clone(),
+            Ok(_) => return Err(BearerError {
+                error: Some(BearerErrorCode::InvalidToken),
+                error_description: Some("The access token is not valid".to_owned()),
+                error_uri: None,
+                scope: None,
+                unavailable: false,
+            }),
+            Err(_) => return Err(BearerError {
+                error: None,
+                error_description: None,
+                error_uri: None,
+                scope: None,
+                unavailable: true,
+            }),
+        };
+
+        if let Some(required) = required_scope {
+            let granted = introspection.scope.clone().unwrap_or(String::new());
+            if ! scope_is_subset(required, &*granted) {
+                return Err(BearerError {
+                    error: Some(BearerErrorCode::InsufficientScope),
+                    error_description: Some("The access token has insufficient scope"
+                                            .to_owned()),
+                    error_uri: None,
+                    scope: Some(required.to_owned()),
+                    unavailable: false,
+                });
+            }
+        }
+
+        Ok(introspection)
+    }
+
+    /// This finishes a request for a protected resource which failed authorization,
+    /// responding with the appropriate status code and a `WWW-Authenticate` challenge.
+    fn deny_request(&self, mut response: Response, error: BearerError)
+    {
+        let realm = self.get_realm();
+        response.headers_mut().set(WwwAuthenticate(
+            error.as_challenge(realm.as_ref().map(|r| &**r))));
+        *response.status_mut() = error.status();
+        let streaming_response = response.start().unwrap();
+        let _ = streaming_response.end();
+    }
+}
+
+/// Extract a bearer access token from a request for a protected resource.  It may be
+/// in an `Authorization: Bearer` header, an `access_token` parameter in a form-encoded
+/// body, or an `access_token` query parameter (RFC 6750 section 2).  Returns Ok(None)
+/// if there is no access token, and an error if more than one method was used.
+///
+/// The body is only read (and thus consumed) if the request has a form-encoded body.
+pub fn extract_bearer_token(request: &mut Request) -> Result<Option<String>, BearerError>
+{
+    let mut tokens: Vec<String> = Vec::new();
+
+    // Authorization Request Header Field (RFC 6750 section 2.1)
+    if let Some(&Authorization(ref bearer)) = request.headers.get::<Authorization<Bearer>>() {
+        tokens.push(bearer.token.clone());
+    }
+
+    // URI Query Parameter (RFC 6750 section 2.3)
+    if let RequestUri::AbsolutePath(ref path) = request.uri {
+        if let Ok(url) = Url::parse(&*format!("http://DUMMY{}", path)) {
+            for (key,val) in url.query_pairs() {
+                if &*key == "access_token" {
+                    tokens.push(val.into_owned());
+                }
+            }
+        }
+    }
+
+    // Form-Encoded Body Parameter (RFC 6750 section 2.2)
+    let form_encoded = match request.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))) => true,
+        _ => false,
+    };
+    if form_encoded && request.method != Method::Get {
+        let mut body: Vec<u8> = Vec::new();
+        if request.read_to_end(&mut body).is_ok() {
+            if let Ok(body) = String::from_utf8(body) {
+                if let Ok(url) = Url::parse(&*format!("http://DUMMY?{}", body)) {
+                    for (key,val) in url.query_pairs() {
+                        if &*key == "access_token" {
+                            tokens.push(val.into_owned());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Clients MUST NOT use more than one method to transmit the token
+    if tokens.len() > 1 {
+        return Err(BearerError {
+            error: Some(BearerErrorCode::InvalidRequest),
+            error_description: Some("More than one access token was supplied".to_owned()),
+            error_uri: None,
+            scope: None,
+            unavailable: false,
+        });
+    }
+
+    match tokens.pop() {
+        None => Ok(None),
+        Some(t) => {
+            if ! valid_access_token_str(&*t) {
+                return Err(BearerError {
+                    error: Some(BearerErrorCode::InvalidRequest),
+                    error_description: Some("The access token is malformed".to_owned()),
+                    error_uri: None,
+                    scope: None,
+                    unavailable: false,
+                });
+            }
+            Ok(Some(t))
+        }
+    }
+}
@@ -0,0 +1,26 @@
+
+use std::fmt;
+use std::fmt::Display;
+
+/// Response Type, as requested in the authorization request, which determines the
+/// authorization flow.
+///
+/// See RFC 6749 Section 3.1.1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResponseType {
+    /// Authorization code grant (RFC 6749 section 4.1)
+    Code,
+    /// Implicit grant (RFC 6749 section 4.2).  The access token is returned directly
+    /// in the redirect URI fragment.
+    Token,
+}
+
+impl Display for ResponseType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            ResponseType::Code => write!(f, "code"),
+            ResponseType::Token => write!(f, "token"),
+        }
+    }
+}
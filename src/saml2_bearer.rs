@@ -0,0 +1,30 @@
+
+use rustc_serialize::base64::{FromBase64, ToBase64, URL_SAFE};
+
+/// The grant_type value for exchanging a SAML 2.0 assertion at the token endpoint
+pub const SAML2_BEARER_GRANT_TYPE: &'static str =
+    "urn:ietf:params:oauth:grant-type:saml2-bearer";
+
+/// Encode a SAML 2.0 assertion (the XML of a single `<Assertion>` element) for the
+/// `assertion` parameter (RFC 7522 section 2.1)
+pub fn encode_saml2_assertion(assertion: &[u8]) -> String {
+    assertion.to_base64(URL_SAFE)
+}
+
+/// Decode the `assertion` parameter into the XML of the SAML 2.0 assertion, or None if
+/// it is not base64url encoded.  Padding is accepted but not required.
+pub fn decode_saml2_assertion(assertion: &str) -> Option<Vec<u8>> {
+    if assertion.contains('+') || assertion.contains('/') {
+        return None;
+    }
+    assertion.from_base64().ok()
+}
+
+#[test]
+fn test_saml2_assertion_encoding() {
+    let xml = b"<Assertion IssueInstant=\"2011-05-31T13:21:00Z\" ID=\"ef1xsbZxPV2oqjd7\"/>";
+    let encoded = encode_saml2_assertion(xml);
+    assert!(! encoded.contains('='));
+    assert_eq!(decode_saml2_assertion(&*encoded), Some(xml.to_vec()));
+    assert_eq!(decode_saml2_assertion("PEFzc2VydGlvbi8+"), None);
+}
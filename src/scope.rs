@@ -0,0 +1,45 @@
+//! Scope handling for OAuth 2.0 elements (RFC 6749 section 3.3)
+
+/// Split a scope string into its space-delimited scope tokens.  Empty tokens (from
+/// repeated spaces) are skipped.
+pub fn scope_tokens<'a>(scope: &'a str) -> Vec<&'a str> {
+    scope.split('\u{0020}').filter(|t| t.len() > 0).collect()
+}
+
+/// Returns true if every scope token in `requested` is also present in `granted`.
+/// Order of the tokens does not matter.
+pub fn scope_is_subset<'a>(requested: &'a str, granted: &'a str) -> bool {
+    let granted_tokens = scope_tokens(granted);
+    for t in scope_tokens(requested) {
+        if ! granted_tokens.contains(&t) { return false; }
+    }
+    true
+}
+
+/// Returns true if `scope` contains the scope token `token`
+pub fn scope_contains<'a>(scope: &'a str, token: &'a str) -> bool {
+    scope_tokens(scope).contains(&token)
+}
+
+#[test]
+fn test_scope_tokens() {
+    assert_eq!(scope_tokens("read write"), vec!["read", "write"]);
+    assert_eq!(scope_tokens(" read  write "), vec!["read", "write"]);
+    assert!(scope_tokens("").is_empty());
+}
+
+#[test]
+fn test_scope_is_subset() {
+    assert!(scope_is_subset("read", "read write"));
+    assert!(scope_is_subset("write read", "read write"));
+    assert!(scope_is_subset("", "read write"));
+    assert!(! scope_is_subset("read delete", "read write"));
+    assert!(! scope_is_subset("read", ""));
+}
+
+#[test]
+fn test_scope_contains() {
+    assert!(scope_contains("openid profile", "openid"));
+    assert!(! scope_contains("openid profile", "email"));
+    assert!(! scope_contains("openidprofile", "openid"));
+}
@@ -70,6 +70,16 @@ pub fn valid_refresh_token_str<'a>(refresh_token: &'a str) -> bool {
     refresh_token.len() > 0 && str_is_vschar(refresh_token)
 }
 
+/// code_verifier as defined in RFC 7636 section 4.1
+pub fn valid_code_verifier_str<'a>(code_verifier: &'a str) -> bool {
+    code_verifier.len() >= 43 && code_verifier.len() <= 128 && str_is_unreserved(code_verifier)
+}
+
+/// code_challenge as defined in RFC 7636 section 4.2
+pub fn valid_code_challenge_str<'a>(code_challenge: &'a str) -> bool {
+    code_challenge.len() >= 43 && code_challenge.len() <= 128 && str_is_unreserved(code_challenge)
+}
+
 
 /// Returns true if c is a digit
 fn char_is_digit(c: char) -> bool {
@@ -151,6 +161,29 @@ fn test_str_is_digit_alpha_under() {
 }
 
 
+/// Returns true if char meets RFC 3986 definition for unreserved (as used by RFC 7636)
+fn char_is_unreserved(c: char) -> bool {
+    match c {
+        '-' | '.' | '_' | '~' | '0'...'9' | 'A'...'Z' | 'a'...'z' => true,
+        _ => false,
+    }
+}
+/// Returns true if string meets RFC 3986 definition for unreserved (as used by RFC 7636)
+fn str_is_unreserved<'a>(v: &'a str) -> bool {
+    let vec: Vec<char> = v.chars().collect();
+    for c in vec.iter() {
+        if ! char_is_unreserved(*c) { return false; }
+    }
+    true
+}
+#[test]
+fn test_str_is_unreserved() {
+    assert!(str_is_unreserved(""));
+    assert!(str_is_unreserved("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk~."));
+    assert!(! str_is_unreserved("abc+def"));
+    assert!(! str_is_unreserved("abc/def="));
+}
+
 /// Returns true if char meets RFC 6749 Appendix A definition for VSCHAR
 fn char_is_vschar(c: char) -> bool {
     match c {
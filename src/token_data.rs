@@ -6,6 +6,11 @@ pub struct TokenData {
     pub expires_in: Option<u32>,
     pub refresh_token: Option<String>,
     pub scope: Option<String>,
+    /// OpenID Connect ID token, if the `openid` scope was granted
+    pub id_token: Option<String>,
+    /// The type of the token issued by a token exchange (RFC 8693 section 2.2.1), as a
+    /// token type identifier
+    pub issued_token_type: Option<String>,
 }
 
 impl TokenData {
@@ -15,8 +20,9 @@ impl TokenData {
     /// will deserialize missing fields as None, so we can use serde for the
     /// reverse.
     pub fn as_json(&self) -> String {
-        let mut json_str = format!("{{\r\n  \"access_token\": \"{}\",\r\n  \"token_type\": \"{}\"",
-                               self.access_token, self.token_type);
+        let mut json_str = format!("{{\r\n  \"access_token\": {},\r\n  \"token_type\": {}",
+                                   ::serde_json::to_string(&self.access_token).unwrap(),
+                                   ::serde_json::to_string(&self.token_type).unwrap());
 
         if self.expires_in.is_some() {
             json_str.push_str( &*format!(",\r\n  \"expires_in\": {}",
@@ -24,11 +30,23 @@ impl TokenData {
         }
         if self.refresh_token.is_some() {
             json_str.push_str( &*format!(",\r\n  \"refresh_token\": {}",
-                                     self.refresh_token.as_ref().unwrap()) );
+                                     ::serde_json::to_string(
+                                         self.refresh_token.as_ref().unwrap()).unwrap()) );
         }
         if self.scope.is_some() {
             json_str.push_str( &*format!(",\r\n  \"scope\": {}",
-                                     self.scope.as_ref().unwrap()) );
+                                     ::serde_json::to_string(
+                                         self.scope.as_ref().unwrap()).unwrap()) );
+        }
+        if self.id_token.is_some() {
+            json_str.push_str( &*format!(",\r\n  \"id_token\": {}",
+                                     ::serde_json::to_string(
+                                         self.id_token.as_ref().unwrap()).unwrap()) );
+        }
+        if self.issued_token_type.is_some() {
+            json_str.push_str( &*format!(",\r\n  \"issued_token_type\": {}",
+                                     ::serde_json::to_string(
+                                         self.issued_token_type.as_ref().unwrap()).unwrap()) );
         }
         json_str.push_str("\r\n}");
         json_str
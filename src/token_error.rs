@@ -13,6 +13,26 @@ pub enum TokenErrorCode {
     UnsupportedGrantType,
     #[serde(rename="invalid_scope")]
     InvalidScope,
+    /// RFC 7009 section 2.2.1
+    #[serde(rename="unsupported_token_type")]
+    UnsupportedTokenType,
+    /// RFC 8628 section 3.5
+    #[serde(rename="authorization_pending")]
+    AuthorizationPending,
+    #[serde(rename="slow_down")]
+    SlowDown,
+    #[serde(rename="access_denied")]
+    AccessDenied,
+    #[serde(rename="expired_token")]
+    ExpiredToken,
+    /// RFC 8693 section 2.2.2
+    #[serde(rename="invalid_target")]
+    InvalidTarget,
+    /// CIBA Core section 13
+    #[serde(rename="unknown_user_id")]
+    UnknownUserId,
+    #[serde(rename="invalid_binding_message")]
+    InvalidBindingMessage,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -29,16 +49,18 @@ impl TokenError {
     /// will deserialize missing fields as None, so we can use serde for the
     /// reverse.
     pub fn as_json(&self) -> String {
-        let mut json_str = format!("{{\r\n  \"error\": \"{}\"",
+        let mut json_str = format!("{{\r\n  \"error\": {}",
                                    ::serde_json::to_string(&self.error).unwrap());
 
         if self.error_description.is_some() {
             json_str.push_str( &*format!(",\r\n  \"error_description\": {}",
-                                     self.error_description.as_ref().unwrap()) );
+                                     ::serde_json::to_string(
+                                         self.error_description.as_ref().unwrap()).unwrap()) );
         }
         if self.error_uri.is_some() {
             json_str.push_str( &*format!(",\r\n  \"error_uri\": {}",
-                                     self.error_uri.as_ref().unwrap()) );
+                                     ::serde_json::to_string(
+                                         self.error_uri.as_ref().unwrap()).unwrap()) );
         }
         json_str.push_str("\r\n}");
         json_str
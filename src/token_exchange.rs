@@ -0,0 +1,252 @@
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use url::Url;
+use url::form_urlencoded::Serializer;
+use syntax::valid_scope_str;
+use {TokenError, TokenErrorCode};
+
+/// The grant_type value for token exchange at the token endpoint
+pub const TOKEN_EXCHANGE_GRANT_TYPE: &'static str =
+    "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// Token Type Identifier, describing the type of a token being exchanged or issued
+/// (RFC 8693 section 3)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenType {
+    /// An OAuth 2.0 access token
+    AccessToken,
+    /// An OAuth 2.0 refresh token
+    RefreshToken,
+    /// An OpenID Connect ID token
+    IdToken,
+    /// A base64url-encoded SAML 1.1 assertion
+    Saml1,
+    /// A base64url-encoded SAML 2.0 assertion
+    Saml2,
+    /// A JWT (RFC 7519)
+    Jwt,
+}
+
+impl TokenType {
+    /// Parse a token type identifier, returning None for unknown types
+    pub fn from_str(token_type: &str) -> Option<TokenType> {
+        match token_type {
+            "urn:ietf:params:oauth:token-type:access_token" => Some(TokenType::AccessToken),
+            "urn:ietf:params:oauth:token-type:refresh_token" => Some(TokenType::RefreshToken),
+            "urn:ietf:params:oauth:token-type:id_token" => Some(TokenType::IdToken),
+            "urn:ietf:params:oauth:token-type:saml1" => Some(TokenType::Saml1),
+            "urn:ietf:params:oauth:token-type:saml2" => Some(TokenType::Saml2),
+            "urn:ietf:params:oauth:token-type:jwt" => Some(TokenType::Jwt),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            TokenType::AccessToken => write!(f, "urn:ietf:params:oauth:token-type:access_token"),
+            TokenType::RefreshToken =>
+                write!(f, "urn:ietf:params:oauth:token-type:refresh_token"),
+            TokenType::IdToken => write!(f, "urn:ietf:params:oauth:token-type:id_token"),
+            TokenType::Saml1 => write!(f, "urn:ietf:params:oauth:token-type:saml1"),
+            TokenType::Saml2 => write!(f, "urn:ietf:params:oauth:token-type:saml2"),
+            TokenType::Jwt => write!(f, "urn:ietf:params:oauth:token-type:jwt"),
+        }
+    }
+}
+
+/// Whether the token issued by a token exchange lets the client act as the subject
+/// (impersonation), or act on the subject's behalf while remaining identifiable as
+/// the actor (delegation).  See RFC 8693 section 1.1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenExchangeMode {
+    /// The issued token represents the subject alone
+    Impersonation,
+    /// The issued token represents the subject, with the actor identified in an `act`
+    /// claim (RFC 8693 section 4.1)
+    Delegation,
+}
+
+/// A request to exchange one token for another at the token endpoint (RFC 8693 section
+/// 2.1), such as a service trading a user's access token for a narrower token to call
+/// a downstream service with.
+#[derive(Clone, Debug)]
+pub struct TokenExchangeRequest {
+    /// The token representing the party on whose behalf the request is made
+    pub subject_token: String,
+    pub subject_token_type: TokenType,
+    /// The token representing the acting party, for delegation
+    pub actor_token: Option<String>,
+    pub actor_token_type: Option<TokenType>,
+    /// The type of token wanted.  If None, the Authorization Server decides.
+    pub requested_token_type: Option<TokenType>,
+    /// Logical names of the services the token is intended for
+    pub audience: Vec<String>,
+    /// Absolute URIs of the resources the token is intended for
+    pub resource: Vec<String>,
+    /// Space-delimited scope wanted
+    pub scope: Option<String>,
+}
+
+impl TokenExchangeRequest {
+    /// Create a request to exchange `subject_token`, leaving the other parameters to
+    /// be filled in.
+    pub fn new(subject_token: &str, subject_token_type: TokenType) -> TokenExchangeRequest {
+        TokenExchangeRequest {
+            subject_token: subject_token.to_owned(),
+            subject_token_type: subject_token_type,
+            actor_token: None,
+            actor_token_type: None,
+            requested_token_type: None,
+            audience: Vec::new(),
+            resource: Vec::new(),
+            scope: None,
+        }
+    }
+
+    /// Read the request from the token endpoint parameters, in the order they were sent
+    /// (as `audience` and `resource` may be repeated), checking that the required
+    /// parameters are present and valid.
+    pub fn from_params(pairs: &[(String, String)]) -> Result<TokenExchangeRequest, TokenError>
+    {
+        let params: HashMap<String, String> = pairs.iter().cloned().collect();
+        let values = |name: &str| -> Vec<String> {
+            pairs.iter().filter(|&&(ref k, _)| k == name).map(|&(_, ref v)| v.clone()).collect()
+        };
+
+        let subject_token = match params.get("subject_token") {
+            Some(t) => t.clone(),
+            None => return Err(invalid_request("subject_token parameter must be supplied \
+                                                in body")),
+        };
+        let subject_token_type = match params.get("subject_token_type")
+            .and_then(|t| TokenType::from_str(&*t))
+        {
+            Some(t) => t,
+            None => return Err(invalid_request("Missing or unsupported subject_token_type")),
+        };
+
+        // actor_token_type is required with, and only with, actor_token
+        let actor_token = params.get("actor_token").cloned();
+        let actor_token_type = match (actor_token.is_some(), params.get("actor_token_type")) {
+            (false, None) => None,
+            (true, Some(t)) => match TokenType::from_str(&*t) {
+                Some(t) => Some(t),
+                None => return Err(invalid_request("Unsupported actor_token_type")),
+            },
+            _ => return Err(invalid_request("actor_token_type must be supplied with, and \
+                                             only with, actor_token")),
+        };
+
+        let requested_token_type = match params.get("requested_token_type") {
+            None => None,
+            Some(t) => match TokenType::from_str(&*t) {
+                Some(t) => Some(t),
+                None => return Err(invalid_request("Unsupported requested_token_type")),
+            },
+        };
+
+        let scope = params.get("scope").cloned();
+        if let Some(ref scope) = scope {
+            if ! valid_scope_str(scope) {
+                return Err(TokenError {
+                    error: TokenErrorCode::InvalidScope,
+                    error_description: None,
+                    error_uri: None,
+                });
+            }
+        }
+
+        // A resource must be an absolute URI without a fragment (RFC 8693 section 2.1)
+        let resource = values("resource");
+        for r in &resource {
+            match Url::parse(r) {
+                Ok(ref url) if url.fragment().is_none() => {},
+                _ => return Err(TokenError {
+                    error: TokenErrorCode::InvalidTarget,
+                    error_description: Some("resource must be an absolute URI".to_owned()),
+                    error_uri: None,
+                }),
+            }
+        }
+
+        Ok(TokenExchangeRequest {
+            subject_token: subject_token,
+            subject_token_type: subject_token_type,
+            actor_token: actor_token,
+            actor_token_type: actor_token_type,
+            requested_token_type: requested_token_type,
+            audience: values("audience"),
+            resource: resource,
+            scope: scope,
+        })
+    }
+
+    /// The url-encoded body of the request to the token endpoint
+    pub fn to_form(&self) -> String {
+        let mut serializer = Serializer::new(String::new());
+        serializer.append_pair("grant_type", TOKEN_EXCHANGE_GRANT_TYPE)
+            .append_pair("subject_token", &*self.subject_token)
+            .append_pair("subject_token_type", &*format!("{}", self.subject_token_type));
+        if let (Some(token), Some(token_type)) = (self.actor_token.as_ref(),
+                                                  self.actor_token_type) {
+            serializer.append_pair("actor_token", token)
+                .append_pair("actor_token_type", &*format!("{}", token_type));
+        }
+        if let Some(token_type) = self.requested_token_type {
+            serializer.append_pair("requested_token_type", &*format!("{}", token_type));
+        }
+        for audience in &self.audience {
+            serializer.append_pair("audience", audience);
+        }
+        for resource in &self.resource {
+            serializer.append_pair("resource", resource);
+        }
+        if let Some(ref scope) = self.scope {
+            serializer.append_pair("scope", scope);
+        }
+        serializer.finish()
+    }
+}
+
+fn invalid_request(description: &str) -> TokenError {
+    TokenError {
+        error: TokenErrorCode::InvalidRequest,
+        error_description: Some(description.to_owned()),
+        error_uri: None,
+    }
+}
+
+#[test]
+fn test_token_exchange_request_params() {
+    let mut request = TokenExchangeRequest::new("accVkjcJyb4BWCxGsndESCJQbdFMogUC5PbRDqceLTC",
+                                                TokenType::AccessToken);
+    request.audience = vec!["urn:example:cooperation-context".to_owned(),
+                            "urn:example:other-context".to_owned()];
+    request.resource = vec!["https://backend.example.com/api".to_owned()];
+    let form = request.to_form();
+
+    let params: Vec<(String, String)> = ::url::form_urlencoded::parse(form.as_bytes())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    assert_eq!(params[0], ("grant_type".to_owned(), TOKEN_EXCHANGE_GRANT_TYPE.to_owned()));
+    let parsed = TokenExchangeRequest::from_params(&params).unwrap();
+    assert_eq!(parsed.subject_token, request.subject_token);
+    assert_eq!(parsed.subject_token_type, TokenType::AccessToken);
+    assert_eq!(parsed.audience, request.audience);
+    assert_eq!(parsed.resource, request.resource);
+    assert_eq!(parsed.actor_token, None);
+
+    let mut bad_resource = params.clone();
+    bad_resource.push(("resource".to_owned(), "backend".to_owned()));
+    assert_eq!(TokenExchangeRequest::from_params(&bad_resource).unwrap_err().error,
+               TokenErrorCode::InvalidTarget);
+
+    let mut params = params;
+    params.push(("actor_token_type".to_owned(), format!("{}", TokenType::Jwt)));
+    assert!(TokenExchangeRequest::from_params(&params).is_err());
+}
@@ -0,0 +1,65 @@
+
+/// Metadata about a token, as returned from the Authorization Server's introspection
+/// endpoint to a protected resource (RFC 7662 section 2.2).  Only `active` is required;
+/// when it is false, no other fields should be set.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TokenIntrospection {
+    /// Whether the token is currently active (issued, not expired and not revoked)
+    pub active: bool,
+    /// Space-delimited scope associated with the token
+    pub scope: Option<String>,
+    /// Client identifier of the client the token was issued to
+    pub client_id: Option<String>,
+    /// Human-readable identifier of the resource owner who authorized the token
+    pub username: Option<String>,
+    /// Type of the token, e.g. "bearer"
+    pub token_type: Option<String>,
+    /// Expiry time, in seconds since 1970-01-01T00:00:00Z UTC
+    pub exp: Option<u64>,
+    /// Issue time, in seconds since 1970-01-01T00:00:00Z UTC
+    pub iat: Option<u64>,
+    /// Time before which the token is not to be used, in seconds since the epoch
+    pub nbf: Option<u64>,
+    /// Subject of the token, usually a machine-readable identifier of the resource owner
+    pub sub: Option<String>,
+    /// Intended audience of the token
+    pub aud: Option<String>,
+    /// Issuer of the token
+    pub iss: Option<String>,
+    /// Unique identifier of the token
+    pub jti: Option<String>,
+}
+
+impl TokenIntrospection {
+    /// The response for a token which is not active, or which the caller is not
+    /// allowed to know about.
+    pub fn inactive() -> TokenIntrospection {
+        TokenIntrospection::default()
+    }
+
+    /// Serialize as the JSON introspection response, leaving out the fields which are
+    /// None (RFC 7662 section 2.2).  Serde deserializes missing fields as None, so it
+    /// can parse the result.
+    pub fn as_json(&self) -> String {
+        let mut json_str = format!("{{\r\n  \"active\": {}", self.active);
+
+        let strings = [("scope", &self.scope), ("client_id", &self.client_id),
+                       ("username", &self.username), ("token_type", &self.token_type),
+                       ("sub", &self.sub), ("aud", &self.aud), ("iss", &self.iss),
+                       ("jti", &self.jti)];
+        for &(name, value) in strings.iter() {
+            if let Some(ref v) = *value {
+                json_str.push_str( &*format!(",\r\n  \"{}\": {}", name,
+                                             ::serde_json::to_string(v).unwrap()) );
+            }
+        }
+        let numbers = [("exp", &self.exp), ("iat", &self.iat), ("nbf", &self.nbf)];
+        for &(name, value) in numbers.iter() {
+            if let Some(v) = *value {
+                json_str.push_str( &*format!(",\r\n  \"{}\": {}", name, v) );
+            }
+        }
+        json_str.push_str("\r\n}");
+        json_str
+    }
+}
@@ -0,0 +1,39 @@
+
+use std::fmt;
+use std::fmt::Display;
+
+/// Token Type Hint, supplied by a client to help the Authorization Server look up a
+/// token it is revoking or introspecting.
+///
+/// See RFC 7009 Section 2.1 and RFC 7662 Section 2.1.  The hint is only a hint; the
+/// Authorization Server should extend its search to other token types if the token
+/// is not found under the hinted type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenTypeHint {
+    /// An access token as defined in RFC 6749 Section 1.4
+    AccessToken,
+    /// A refresh token as defined in RFC 6749 Section 1.5
+    RefreshToken,
+}
+
+impl TokenTypeHint {
+    /// Parse the `token_type_hint` parameter, returning None for unknown hints
+    /// (which the Authorization Server may ignore).
+    pub fn from_str(hint: &str) -> Option<TokenTypeHint> {
+        match hint {
+            "access_token" => Some(TokenTypeHint::AccessToken),
+            "refresh_token" => Some(TokenTypeHint::RefreshToken),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TokenTypeHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self {
+            TokenTypeHint::AccessToken => write!(f, "access_token"),
+            TokenTypeHint::RefreshToken => write!(f, "refresh_token"),
+        }
+    }
+}
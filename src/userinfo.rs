@@ -0,0 +1,48 @@
+
+use std::collections::BTreeMap;
+use serde_json::Value;
+use scope::scope_tokens;
+
+/// The standard claims which each OpenID Connect scope value requests access to
+/// (OpenID Connect Core section 5.4)
+pub fn scope_claims(scope_token: &str) -> &'static [&'static str] {
+    match scope_token {
+        "profile" => &["name", "family_name", "given_name", "middle_name", "nickname",
+                       "preferred_username", "profile", "picture", "website", "gender",
+                       "birthdate", "zoneinfo", "locale", "updated_at"],
+        "email" => &["email", "email_verified"],
+        "address" => &["address"],
+        "phone" => &["phone_number", "phone_number_verified"],
+        _ => &[],
+    }
+}
+
+/// Filter claims about an end-user down to those that the given scope grants access
+/// to.  `sub` is always kept.
+pub fn filter_claims(claims: BTreeMap<String, Value>, scope: &str)
+                     -> BTreeMap<String, Value>
+{
+    let tokens = scope_tokens(scope);
+    claims.into_iter().filter(|&(ref name, _)| {
+        name == "sub" || tokens.iter().any(|t| scope_claims(t).contains(&&**name))
+    }).collect()
+}
+
+#[test]
+fn test_filter_claims() {
+    let mut claims: BTreeMap<String, Value> = BTreeMap::new();
+    claims.insert("sub".to_owned(), Value::String("248289761001".to_owned()));
+    claims.insert("name".to_owned(), Value::String("Jane Doe".to_owned()));
+    claims.insert("email".to_owned(), Value::String("janedoe@example.com".to_owned()));
+    claims.insert("email_verified".to_owned(), Value::Bool(true));
+    claims.insert("phone_number".to_owned(), Value::String("+1 (425) 555-1212".to_owned()));
+
+    let filtered = filter_claims(claims.clone(), "openid email");
+    assert_eq!(filtered.len(), 3);
+    assert!(filtered.contains_key("sub"));
+    assert!(filtered.contains_key("email"));
+    assert!(filtered.contains_key("email_verified"));
+
+    let filtered = filter_claims(claims, "openid");
+    assert_eq!(filtered.len(), 1);
+}
@@ -3,72 +3,136 @@ extern crate oauth2;
 extern crate hyper;
 extern crate url;
 extern crate textnonce;
+extern crate serde_json;
 
 use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::error::Error as StdError;
-use std::fmt;
-use oauth2::{ClientData, AuthzServer, TokenData, Client, ClientType,
-             AuthzError, AuthzErrorCode, UserError, OAuthError, ClientId,
-             RedirectUri};
-use hyper::server::{Handler, Request, Response};
+use std::io::Read;
+use oauth2::{ClientData, AuthzServer, TokenData, Client, ClientType, ClientAuthnMethod,
+             AuthzError, AuthzErrorCode, OAuthError, ClientId, RedirectUri,
+             ClientAuthorization, AuthzServerMetadata, TokenError, TokenErrorCode,
+             TokenTypeHint, TokenIntrospection, TokenType, TokenExchangeMode,
+             TokenExchangeRequest, Jwt, HmacKey, KeySet, DevicePollResult, DeliveryMode,
+             BackchannelAuthRequest, BackchannelAuthResult, ClientRegistration,
+             RegistrationError, RegistrationErrorCode};
+use oauth2::client_authn_method::{sign_client_assertion, CLIENT_ASSERTION_TYPE};
+use oauth2::jwt_bearer::sign_jwt_assertion;
+use oauth2::device_authorization::DEVICE_CODE_GRANT_TYPE;
+use oauth2::backchannel_authentication::CIBA_GRANT_TYPE;
+use hyper::server::{Handler, Listening, Request, Response};
+use hyper::client::Client as HttpClient;
+use hyper::header::{Authorization, Bearer, ContentType};
+use hyper::method::Method;
 use hyper::status::StatusCode;
 use hyper::uri::RequestUri;
 use url::Url;
+use url::form_urlencoded::Serializer;
 use textnonce::TextNonce;
 
+// The key trusted to sign JWT assertions issued by the "service" account
+const SERVICE_KEY: &'static [u8] = b"service account secret";
+
+// The only SAML 2.0 assertion the test server accepts
+const SAML2_ASSERTION: &'static [u8] = b"<saml:Assertion>alice</saml:Assertion>";
+
 #[derive(Clone, Copy, PartialEq)]
 enum InjectedFailure {
     NotAuthorized,
     NoSuchClient,
 }
 
-// Dummied up error type
-#[derive(Debug)]
-struct MyError(String);
-impl StdError for MyError {
-    fn description(&self) -> &str {
-        "error"
-    }
-    fn cause(&self) -> Option<&StdError> {
-        None
-    }
-}
-impl fmt::Display for MyError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+fn test_client_data(client_id: &str, client_port: u16) -> ClientData {
+    let (client_type, credentials, authn_method, delivery_mode) = match client_id {
+        "post" => (ClientType::ConfidentialClient, "hoo", ClientAuthnMethod::ClientSecretPost,
+                   None),
+        "public" => (ClientType::PublicClient, "", ClientAuthnMethod::None, None),
+        "jwt" => (ClientType::ConfidentialClient, "a shared secret for signing",
+                  ClientAuthnMethod::ClientSecretJwt, None),
+        "ciba" => (ClientType::ConfidentialClient, "woo", ClientAuthnMethod::ClientSecretBasic,
+                   Some(DeliveryMode::Poll)),
+        _ => (ClientType::ConfidentialClient, "boo", ClientAuthnMethod::ClientSecretBasic,
+              None),
+    };
+    ClientData {
+        client_id: ClientId(client_id.to_owned()),
+        client_type: client_type,
+        redirect_uri: vec![
+            RedirectUri(format!("http://127.0.0.1:{}/redirect_uri", client_port)) ],
+        credentials: credentials.to_owned(),
+        // Clients "read" and "ciba" are only allowed the read and openid scopes
+        scope: match client_id {
+            "read" => Some("read".to_owned()),
+            "ciba" => Some("openid".to_owned()),
+            _ => None,
+        },
+        authn_method: authn_method,
+        par_required: false,
+        request_object_required: false,
+        backchannel_delivery_mode: delivery_mode,
+        backchannel_notification_endpoint: None
     }
 }
-impl UserError for MyError { }
 
 struct MyAuthzServer {
+    pub issuer: String,
     pub registered_clients: HashMap<ClientId, ClientData>,
-    pub client_authorizations: HashMap<String, (ClientId, RedirectUri)>, // code => client_id, redirect_uri
+    pub client_authorizations: HashMap<String, ClientAuthorization>, // code => authorization
+    pub access_tokens: HashMap<String, (ClientId, Option<String>)>, // token => client, scope
+    pub refresh_tokens: HashMap<String, (ClientId, Option<String>)>, // token => client, scope
+    pub device_authorizations: HashMap<String, (ClientId, bool)>, // device_code => approved
+    pub backchannel_requests: HashMap<String, (ClientId, Option<String>)>, // => access token
+    pub pushed_requests: RefCell<HashMap<String, HashMap<String, String>>>,
+    pub assertion_jtis: RefCell<HashSet<String>>,
+    pub registrations: HashMap<ClientId, ClientRegistration>,
     pub failure: Option<InjectedFailure>
 }
 impl MyAuthzServer {
-    pub fn new(client_port: u16, failure: Option<InjectedFailure>) -> MyAuthzServer {
+    pub fn new(server_port: u16, client_port: u16, failure: Option<InjectedFailure>)
+               -> MyAuthzServer
+    {
         let mut rc: HashMap<ClientId, ClientData> = HashMap::new();
-        rc.insert(ClientId("1".to_string()),
-                  ClientData {
-                      client_id: ClientId("1".to_string()),
-                      client_type: ClientType::ConfidentialClient,
-                      redirect_uri: vec![
-                          RedirectUri(format!("http://127.0.0.1:{}/redirect_uri", client_port)) ],
-                      credentials: "boo".to_owned(),
-                      authn_scheme: None
-                  });
+        for client_id in &["1", "read", "post", "public", "jwt", "ciba"] {
+            rc.insert(ClientId(client_id.to_string()), test_client_data(client_id, client_port));
+        }
 
         MyAuthzServer {
+            issuer: format!("http://127.0.0.1:{}", server_port),
             registered_clients: rc,
             client_authorizations: HashMap::new(),
+            access_tokens: HashMap::new(),
+            refresh_tokens: HashMap::new(),
+            device_authorizations: HashMap::new(),
+            backchannel_requests: HashMap::new(),
+            pushed_requests: RefCell::new(HashMap::new()),
+            assertion_jtis: RefCell::new(HashSet::new()),
+            registrations: HashMap::new(),
             failure: failure
         }
     }
+
+    // Issue an access token (and optionally a refresh token), recording it so that it
+    // can be refreshed, revoked and introspected
+    pub fn issue_token(&mut self, client_id: &ClientId, scope: Option<&str>, refresh: bool)
+                       -> TokenData
+    {
+        let scope = scope.map(|s| s.to_owned());
+        let access_token = TextNonce::sized_urlsafe(32).unwrap().into_string();
+        self.access_tokens.insert(access_token.clone(), (client_id.clone(), scope.clone()));
+        let refresh_token = if refresh {
+            let refresh_token = TextNonce::sized_urlsafe(32).unwrap().into_string();
+            self.refresh_tokens.insert(refresh_token.clone(),
+                                       (client_id.clone(), scope.clone()));
+            Some(refresh_token)
+        } else {
+            None
+        };
+        bearer_token(access_token, refresh_token, scope)
+    }
 }
-impl AuthzServer<(),MyError> for MyAuthzServer {
+impl AuthzServer<()> for MyAuthzServer {
     fn fetch_client_data(&self, _context: &mut (), client_id: &ClientId)
-                         -> Result<Option<ClientData>, OAuthError<MyError>>
+                         -> Result<Option<ClientData>, OAuthError>
     {
         if self.failure == Some(InjectedFailure::NoSuchClient) {
             return Ok(None);
@@ -77,27 +141,275 @@ impl AuthzServer<(),MyError> for MyAuthzServer {
     }
 
     fn retrieve_client_authorization(&self, _context: &mut (), code: &str)
-                                     -> Result<(ClientId, RedirectUri), OAuthError<MyError>>
+                                     -> Result<ClientAuthorization, OAuthError>
     {
         match self.client_authorizations.get(code) {
             None => Err(OAuthError::AuthzUnknownClient),
-            Some(&(ref client_id, ref redirect_uri)) =>
-                Ok((client_id.clone(), redirect_uri.clone()))
+            Some(authorization) => Ok(authorization.clone())
+        }
+    }
+
+    fn issue_token_to_client(&mut self, _context: &mut (), _code: &str, client_id: &ClientId)
+                             -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, None, false))
+    }
+
+    fn retrieve_refresh_token(&self, _context: &mut (), refresh_token: &str)
+                              -> Result<(ClientId, Option<String>), OAuthError>
+    {
+        match self.refresh_tokens.get(refresh_token) {
+            None => Err(OAuthError::AuthzGrantNotFound),
+            Some(&(ref client_id, ref scope)) => Ok((client_id.clone(), scope.clone()))
+        }
+    }
+
+    fn rotate_refresh_token(&mut self, _context: &mut (), refresh_token: &str,
+                            client_id: &ClientId, scope: Option<&str>)
+                            -> Result<TokenData, OAuthError>
+    {
+        self.refresh_tokens.remove(refresh_token);
+        Ok(self.issue_token(client_id, scope, true))
+    }
+
+    fn issue_token_for_client_credentials(&mut self, _context: &mut (), client_id: &ClientId,
+                                          scope: Option<&str>)
+                                          -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, scope, false))
+    }
+
+    fn allow_password_grant(&self, _context: &mut (), _client_data: &ClientData) -> bool {
+        true
+    }
+
+    fn verify_resource_owner_credentials(&mut self, _context: &mut (), username: &str,
+                                         password: &str) -> Result<bool, OAuthError>
+    {
+        Ok(username == "alice" && password == "wonderland")
+    }
+
+    fn issue_token_for_resource_owner(&mut self, _context: &mut (), client_id: &ClientId,
+                                      _username: &str, scope: Option<&str>)
+                                      -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, scope, true))
+    }
+
+    fn revoke_token(&mut self, _context: &mut (), client_id: &ClientId, token: &str,
+                    _token_type_hint: Option<TokenTypeHint>) -> Result<(), OAuthError>
+    {
+        if self.access_tokens.get(token).map(|t| &t.0) == Some(client_id) {
+            self.access_tokens.remove(token);
+        }
+        if self.refresh_tokens.get(token).map(|t| &t.0) == Some(client_id) {
+            self.refresh_tokens.remove(token);
+        }
+        Ok(())
+    }
+
+    fn introspect_token(&self, _context: &mut (), _client_id: &ClientId, token: &str,
+                        _token_type_hint: Option<TokenTypeHint>)
+                        -> Result<TokenIntrospection, OAuthError>
+    {
+        match self.access_tokens.get(token) {
+            None => Ok(TokenIntrospection::inactive()),
+            Some(&(ref client_id, ref scope)) => Ok(TokenIntrospection {
+                active: true,
+                scope: scope.clone(),
+                client_id: Some((**client_id).clone()),
+                token_type: Some("bearer".to_owned()),
+                .. TokenIntrospection::default()
+            })
+        }
+    }
+
+    fn get_metadata(&self, _context: &mut ()) -> Option<AuthzServerMetadata> {
+        Some(AuthzServerMetadata::new(&*self.issuer,
+                                      &*format!("{}/authorization", self.issuer),
+                                      &*format!("{}/token", self.issuer)))
+    }
+
+    fn get_device_verification_uri(&self, _context: &mut ()) -> Option<String> {
+        Some(format!("{}/device/verify", self.issuer))
+    }
+
+    fn store_device_authorization(&mut self, _context: &mut (), client_id: &ClientId,
+                                  _scope: Option<&str>, device_code: &str, _user_code: &str,
+                                  _expires_in: u64) -> Result<(), OAuthError>
+    {
+        self.device_authorizations.insert(device_code.to_owned(), (client_id.clone(), false));
+        Ok(())
+    }
+
+    fn poll_device_authorization(&mut self, _context: &mut (), client_id: &ClientId,
+                                 device_code: &str) -> Result<DevicePollResult, OAuthError>
+    {
+        match self.device_authorizations.get(device_code).cloned() {
+            Some((ref cid, false)) if cid == client_id => Ok(DevicePollResult::Pending),
+            Some((ref cid, true)) if cid == client_id => {
+                self.device_authorizations.remove(device_code);
+                Ok(DevicePollResult::Granted(self.issue_token(client_id, None, false)))
+            },
+            _ => Err(OAuthError::AuthzGrantNotFound)
+        }
+    }
+
+    fn notify_backchannel_user(&mut self, _context: &mut (), client_data: &ClientData,
+                               request: &BackchannelAuthRequest, auth_req_id: &str,
+                               _expires_in: u64) -> Result<(), OAuthError>
+    {
+        if request.login_hint.as_ref().map(|h| &**h) != Some("alice") {
+            return Err(OAuthError::AuthzUnknownUser);
+        }
+        self.backchannel_requests.insert(auth_req_id.to_owned(),
+                                         (client_data.client_id.clone(), None));
+        Ok(())
+    }
+
+    fn record_backchannel_decision(&mut self, _context: &mut (), _client_id: &ClientId,
+                                   auth_req_id: &str, result: &BackchannelAuthResult)
+                                   -> Result<(), OAuthError>
+    {
+        match (self.backchannel_requests.get_mut(auth_req_id), result) {
+            (Some(request), &BackchannelAuthResult::Granted(ref token)) => {
+                request.1 = Some(token.access_token.clone());
+                Ok(())
+            },
+            _ => Err(OAuthError::AuthzGrantNotFound)
+        }
+    }
+
+    fn poll_backchannel_authentication(&mut self, _context: &mut (), client_id: &ClientId,
+                                       auth_req_id: &str)
+                                       -> Result<BackchannelAuthResult, OAuthError>
+    {
+        match self.backchannel_requests.get(auth_req_id).cloned() {
+            Some((ref cid, None)) if cid == client_id => Ok(BackchannelAuthResult::Pending),
+            Some((ref cid, Some(ref access_token))) if cid == client_id => {
+                self.backchannel_requests.remove(auth_req_id);
+                Ok(BackchannelAuthResult::Granted(
+                    bearer_token(access_token.clone(), None, None)))
+            },
+            _ => Err(OAuthError::AuthzGrantNotFound)
+        }
+    }
+
+    fn get_assertion_key_set(&self, _context: &mut (), issuer: &str)
+                             -> Result<Option<Box<KeySet>>, OAuthError>
+    {
+        if issuer != "service" {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(vec![HmacKey { key: SERVICE_KEY.to_vec(), key_id: None }])))
+    }
+
+    fn issue_token_for_jwt_assertion(&mut self, _context: &mut (), client_id: &ClientId,
+                                     _assertion: &Jwt, scope: Option<&str>)
+                                     -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, scope, false))
+    }
+
+    fn validate_saml2_assertion(&mut self, _context: &mut (), _client_id: &ClientId,
+                                assertion: &[u8]) -> Result<String, OAuthError>
+    {
+        if assertion != SAML2_ASSERTION {
+            return Err(OAuthError::AuthzBadRequest);
         }
+        Ok("alice".to_owned())
+    }
+
+    fn issue_token_for_saml2_assertion(&mut self, _context: &mut (), client_id: &ClientId,
+                                       _subject: &str, scope: Option<&str>)
+                                       -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, scope, false))
+    }
+
+    fn decide_token_exchange(&mut self, _context: &mut (), _client_id: &ClientId,
+                             request: &TokenExchangeRequest)
+                             -> Result<TokenExchangeMode, OAuthError>
+    {
+        if ! self.access_tokens.contains_key(&request.subject_token) {
+            return Err(OAuthError::AuthzBadRequest);
+        }
+        Ok(TokenExchangeMode::Impersonation)
+    }
+
+    fn issue_token_for_exchange(&mut self, _context: &mut (), client_id: &ClientId,
+                                request: &TokenExchangeRequest, _mode: TokenExchangeMode)
+                                -> Result<TokenData, OAuthError>
+    {
+        Ok(self.issue_token(client_id, request.scope.as_ref().map(|s| &**s), false))
+    }
+
+    fn store_pushed_authz_request(&mut self, _context: &mut (), request_uri: &str,
+                                  params: &HashMap<String, String>, _expires_in: u64)
+                                  -> Result<(), OAuthError>
+    {
+        self.pushed_requests.borrow_mut().insert(request_uri.to_owned(), params.clone());
+        Ok(())
+    }
+
+    fn retrieve_pushed_authz_request(&self, _context: &mut (), request_uri: &str)
+                                     -> Result<Option<HashMap<String, String>>, OAuthError>
+    {
+        Ok(self.pushed_requests.borrow_mut().remove(request_uri))
+    }
+
+    fn record_client_assertion_jti(&self, _context: &mut (), _client_id: &ClientId,
+                                   jti: &str, _exp: u64) -> Result<bool, OAuthError>
+    {
+        Ok(self.assertion_jtis.borrow_mut().insert(jti.to_owned()))
     }
 
-    fn issue_token_to_client(&mut self, _context: &mut (), _code: &str, _client_id: &ClientId)
-                             -> Result<TokenData, OAuthError<MyError>>
+    fn get_registration_client_uri(&self, _context: &mut (), client_id: &ClientId)
+                                   -> Option<String>
     {
-        let token = TextNonce::new().into_string();
-        // FIXME - save this issuance somewhere and recheck it in test fn
-        Ok(TokenData {
-            access_token: token,
-            token_type: "bearer".to_owned(),
-            expires_in: None,
-            refresh_token: None,
-            scope: None,
-        })
+        Some(format!("{}/register/{}", self.issuer, &**client_id))
+    }
+
+    fn register_client(&mut self, _context: &mut (), client_data: &ClientData,
+                       registration: &ClientRegistration) -> Result<(), OAuthError>
+    {
+        self.registered_clients.insert(client_data.client_id.clone(), client_data.clone());
+        self.registrations.insert(client_data.client_id.clone(), registration.clone());
+        Ok(())
+    }
+
+    fn retrieve_client_registration(&self, _context: &mut (), client_id: &ClientId)
+                                    -> Result<Option<ClientRegistration>, OAuthError>
+    {
+        Ok(self.registrations.get(client_id).cloned())
+    }
+
+    fn update_client_registration(&mut self, context: &mut (), client_data: &ClientData,
+                                  registration: &ClientRegistration) -> Result<(), OAuthError>
+    {
+        self.register_client(context, client_data, registration)
+    }
+
+    fn delete_client_registration(&mut self, _context: &mut (), client_id: &ClientId)
+                                  -> Result<(), OAuthError>
+    {
+        self.registered_clients.remove(client_id);
+        self.registrations.remove(client_id);
+        Ok(())
+    }
+}
+
+fn bearer_token(access_token: String, refresh_token: Option<String>, scope: Option<String>)
+                -> TokenData
+{
+    TokenData {
+        access_token: access_token,
+        token_type: "bearer".to_owned(),
+        expires_in: Some(3600),
+        refresh_token: refresh_token,
+        scope: scope,
+        id_token: None,
+        issued_token_type: None,
     }
 }
 
@@ -124,10 +436,12 @@ impl Handler for MyAuthzHandler {
         };
         let url: Url = Url::parse(&*format!("http://127.0.0.1{}", pqf)).unwrap();
 
+        let mut authz_server = self.authz_server.lock().unwrap();
         match url.path() {
             "/authorization" => {
-                let mut authz_server = self.authz_server.lock().unwrap();
-                match authz_server.handle_authz_request(&mut (), request) {
+                match authz_server.handle_authz_request(
+                    &mut (), &*format!("http://127.0.0.1{}", pqf))
+                {
                     Ok((request_data, option_error)) => {
 
                         // Resolve the redirect_uri
@@ -169,7 +483,8 @@ impl Handler for MyAuthzHandler {
                             // Save the authorization grant
                             authz_server.client_authorizations.insert(
                                 authorization_code.clone(),
-                                (request_data.client_id, redirect_uri.clone()));
+                                ClientAuthorization::from_request(&request_data,
+                                                                  redirect_uri.clone()));
 
                             let _ = authz_server.grant_authz_request(
                                 response, &redirect_uri,
@@ -179,9 +494,21 @@ impl Handler for MyAuthzHandler {
                     Err(_) => self.handle_fail(response, None),
                 }
             },
-            "/token" => {
-                let mut authz_server = self.authz_server.lock().unwrap();
-                authz_server.handle_token_request(&mut (), request, response);
+            "/token" => authz_server.handle_token_request(&mut (), request, response),
+            "/par" => authz_server.handle_pushed_authz_request(&mut (), request, response),
+            "/revoke" => authz_server.handle_revocation_request(&mut (), request, response),
+            "/introspect" =>
+                authz_server.handle_introspection_request(&mut (), request, response),
+            "/device" =>
+                authz_server.handle_device_authorization_request(&mut (), request, response),
+            "/bc-authorize" =>
+                authz_server.handle_backchannel_authentication_request(&mut (), request,
+                                                                       response),
+            "/register" => authz_server.handle_registration_request(&mut (), request, response),
+            path if path.starts_with("/register/") => {
+                let client_id = ClientId(path["/register/".len()..].to_owned());
+                authz_server.handle_client_configuration_request(&mut (), &client_id,
+                                                                 request, response);
             },
             _ => self.handle_fail(response, Some(StatusCode::NotFound))
         }
@@ -190,26 +517,27 @@ impl Handler for MyAuthzHandler {
 
 struct MyClient {
     client_data: ClientData,
+    authn_method: ClientAuthnMethod,
     nonces: HashSet<String>,
+    code_verifiers: HashMap<String, String>,
     server_port: u16,
 }
 impl MyClient {
-    pub fn new(client_port: u16, server_port: u16) -> MyClient {
+    pub fn new(client_data: ClientData, server_port: u16) -> MyClient {
         MyClient {
-            client_data: ClientData {
-                client_id: ClientId("1".to_string()),
-                client_type: ClientType::ConfidentialClient,
-                redirect_uri: vec![
-                    RedirectUri(format!("http://127.0.0.1:{}/redirect_uri", client_port)) ],
-                credentials: "boo".to_owned(),
-                authn_scheme: None
-            },
+            authn_method: client_data.authn_method,
+            client_data: client_data,
             nonces: HashSet::new(),
+            code_verifiers: HashMap::new(),
             server_port: server_port,
         }
     }
+
+    pub fn endpoint(&self, path: &str) -> Url {
+        Url::parse(&*format!("http://127.0.0.1:{}{}", self.server_port, path)).unwrap()
+    }
 }
-impl Client<MyError> for MyClient {
+impl Client for MyClient {
     fn get_client_data<'a>(&'a self) -> &'a ClientData
     {
         &self.client_data
@@ -223,9 +551,21 @@ impl Client<MyError> for MyClient {
         self.nonces.remove(token)
     }
 
+    fn store_code_verifier(&mut self, state: &str, code_verifier: &str) {
+        self.code_verifiers.insert(state.to_owned(), code_verifier.to_owned());
+    }
+
+    fn consume_code_verifier(&mut self, state: &str) -> Option<String> {
+        self.code_verifiers.remove(state)
+    }
+
     fn get_redirect_uri<'a>(&'a self) -> &'a str {
         &self.client_data.redirect_uri[0]
     }
+
+    fn get_authn_method(&self) -> ClientAuthnMethod {
+        self.authn_method
+    }
 }
 
 struct MyClientHandler {
@@ -251,21 +591,22 @@ impl Handler for MyClientHandler {
         };
 
         let mut client = self.client.lock().unwrap();
-        let server_port = client.server_port;
 
         let url: Url = Url::parse(&*format!("http://127.0.0.1:{}", pqf)).unwrap();
 
         match url.path() {
             "/" => {
-                let _ = client.start_oauth(
-                    None,
-                    Url::parse(&*format!("http://127.0.0.1:{}/authorization", server_port)).unwrap(),
-                    response);
+                let authz_url = client.endpoint("/authorization");
+                let _ = client.start_oauth(None, authz_url, response);
+            },
+            "/par" => {
+                let par_url = client.endpoint("/par");
+                let authz_url = client.endpoint("/authorization");
+                let _ = client.push_authz_request(None, par_url, authz_url, response);
             },
             "/redirect_uri" => {
-                match client.handle_redirect_url(
-                    request,
-                    Url::parse(&*format!("http://127.0.0.1:{}/token", server_port)).unwrap())
+                let token_url = client.endpoint("/token");
+                match client.handle_redirect_url(request, token_url)
                 {
                     Ok(result) => match result {
                         Ok(_token_data) => {
@@ -289,24 +630,34 @@ impl Handler for MyClientHandler {
     }
 }
 
-fn run_test(server_port: u16, client_port: u16, failure: Option<InjectedFailure>)
-         -> ::hyper::client::Response
+fn start_server(server_port: u16, client_port: u16, failure: Option<InjectedFailure>)
+                -> (Arc<Mutex<MyAuthzServer>>, Listening)
 {
     use hyper::server::Server;
-    use hyper::client::Client;
 
+    let authz_server = Arc::new( Mutex::new(
+        MyAuthzServer::new(server_port, client_port, failure) ) );
     let server_handler = MyAuthzHandler {
-        authz_server: Arc::new( Mutex::new(
-            MyAuthzServer::new(client_port, failure) ) ),
+        authz_server: authz_server.clone(),
     };
-    let mut listening_server = match Server::http(("127.0.0.1",server_port)) {
+    let listening_server = match Server::http(("127.0.0.1",server_port)) {
         Ok(s) => s,
         Err(e) => panic!("Unable to start test server: {}", e)
     }.handle(server_handler).unwrap();
 
+    (authz_server, listening_server)
+}
+
+fn run_test(server_port: u16, client_port: u16, path: &str,
+            failure: Option<InjectedFailure>) -> ::hyper::client::Response
+{
+    use hyper::server::Server;
+
+    let (_, mut listening_server) = start_server(server_port, client_port, failure);
+
     let client_handler = MyClientHandler {
         client: Arc::new( Mutex::new(
-            MyClient::new(client_port, server_port) ) ),
+            MyClient::new(test_client_data("1", client_port), server_port) ) ),
     };
     let mut listening_client = match Server::http(("127.0.0.1",client_port)) {
         Ok(c) => c,
@@ -314,8 +665,9 @@ fn run_test(server_port: u16, client_port: u16, failure: Option<InjectedFailure>
     }.handle(client_handler).unwrap();
 
     // Browse to client, and be redirected
-    let user_agent = Client::new();
-    let res = user_agent.get(&format!("http://127.0.0.1:{}",client_port)).send().unwrap();
+    let user_agent = HttpClient::new();
+    let res = user_agent.get(&format!("http://127.0.0.1:{}{}", client_port, path))
+        .send().unwrap();
 
     // Close down
     let _ = listening_server.close();
@@ -324,20 +676,570 @@ fn run_test(server_port: u16, client_port: u16, failure: Option<InjectedFailure>
     res
 }
 
+// POST url-encoded parameters to a server endpoint, authenticating with HTTP Basic
+// Authorization as `basic` if given, and return the response status and body
+fn post_form(server_port: u16, path: &str, basic: Option<&ClientData>, body: &str)
+             -> (StatusCode, String)
+{
+    let user_agent = HttpClient::new();
+    let url = format!("http://127.0.0.1:{}{}", server_port, path);
+    let mut request = user_agent.post(&*url).header(ContentType::form_url_encoded());
+    if let Some(client_data) = basic {
+        request = request.header(client_data.http_basic_authentication_generate());
+    }
+    read_response(request.body(body).send().unwrap())
+}
+
+// Make a request to a client configuration endpoint with a registration access token
+fn configure(method: Method, url: &str, token: &str, body: Option<&str>)
+             -> (StatusCode, String)
+{
+    let user_agent = HttpClient::new();
+    let mut request = user_agent.request(method, url)
+        .header(Authorization(Bearer { token: token.to_owned() }));
+    if let Some(body) = body {
+        request = request.header(ContentType::json()).body(body);
+    }
+    read_response(request.send().unwrap())
+}
+
+fn read_response(mut res: ::hyper::client::Response) -> (StatusCode, String) {
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    (res.status, body)
+}
+
+fn token_error_code(body: &str) -> TokenErrorCode {
+    let error: TokenError = serde_json::from_str(body).unwrap();
+    error.error
+}
+
+fn test_client(client_id: &str, server_port: u16) -> MyClient {
+    MyClient::new(test_client_data(client_id, server_port + 1), server_port)
+}
+
 #[test]
 fn test_success() {
-    let res = run_test(12001, 12002, None);
+    let res = run_test(12001, 12002, "/", None);
     assert_eq!(res.status, StatusCode::Ok);
 }
 
 #[test]
 fn test_failure_no_such_client() {
-    let res = run_test(12003, 12004, Some(InjectedFailure::NoSuchClient));
+    let res = run_test(12003, 12004, "/", Some(InjectedFailure::NoSuchClient));
     assert_eq!(res.status, StatusCode::BadRequest);
 }
 
 #[test]
 fn test_failure_not_authorized() {
-    let res = run_test(12005, 12006, Some(InjectedFailure::NotAuthorized));
+    let res = run_test(12005, 12006, "/", Some(InjectedFailure::NotAuthorized));
     assert_eq!(res.status, StatusCode::BadRequest);
 }
+
+#[test]
+fn test_pushed_authz_request() {
+    let res = run_test(12007, 12008, "/par", None);
+    assert_eq!(res.status, StatusCode::Ok);
+}
+
+#[test]
+fn test_pushed_authz_request_rejected() {
+    let (_, mut listening) = start_server(12009, 12010, None);
+
+    // A pushed request cannot itself refer to a pushed request
+    let (status, body) = post_form(12009, "/par", Some(&test_client_data("1", 12010)),
+                                   "response_type=code&request_uri=urn%3Aexample");
+    assert_eq!(status, StatusCode::BadRequest);
+    let error: AuthzError = serde_json::from_str(&*body).unwrap();
+    assert_eq!(error.error, AuthzErrorCode::InvalidRequest);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_secret_basic() {
+    let (_, mut listening) = start_server(12011, 12012, None);
+    let client = test_client("1", 12011);
+
+    let result = client.request_client_credentials_token(None, client.endpoint("/token"));
+    assert!(result.unwrap().is_ok());
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_secret_post() {
+    let (_, mut listening) = start_server(12013, 12014, None);
+    let client = test_client("post", 12013);
+
+    let result = client.request_client_credentials_token(None, client.endpoint("/token"));
+    assert!(result.unwrap().is_ok());
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_authn_none() {
+    let (_, mut listening) = start_server(12015, 12016, None);
+    let client = test_client("public", 12015);
+
+    // Public clients identify themselves with client_id alone
+    let result = client.revoke_token("unknown", None, client.endpoint("/revoke"));
+    assert!(result.unwrap().is_ok());
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_secret_jwt() {
+    let (_, mut listening) = start_server(12017, 12018, None);
+    let client = test_client("jwt", 12017);
+
+    let result = client.request_client_credentials_token(None, client.endpoint("/token"));
+    assert!(result.unwrap().is_ok());
+
+    // The client assertion must be signed with the client's secret
+    let client_data = test_client_data("jwt", 12018);
+    let key = HmacKey { key: b"not the client secret".to_vec(), key_id: None };
+    let assertion = sign_client_assertion(&client_data.client_id,
+                                          "http://127.0.0.1:12017/token", &key).unwrap();
+    let body = Serializer::new(String::new())
+        .append_pair("grant_type", "client_credentials")
+        .append_pair("client_assertion_type", CLIENT_ASSERTION_TYPE)
+        .append_pair("client_assertion", &*assertion)
+        .finish();
+    let (status, response) = post_form(12017, "/token", None, &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::InvalidClient);
+
+    // A client assertion may only be used once
+    let key = HmacKey { key: client_data.credentials.as_bytes().to_vec(), key_id: None };
+    let assertion = sign_client_assertion(&client_data.client_id,
+                                          "http://127.0.0.1:12017/token", &key).unwrap();
+    let body = Serializer::new(String::new())
+        .append_pair("grant_type", "client_credentials")
+        .append_pair("client_assertion_type", CLIENT_ASSERTION_TYPE)
+        .append_pair("client_assertion", &*assertion)
+        .finish();
+    let (status, _) = post_form(12017, "/token", None, &*body);
+    assert_eq!(status, StatusCode::Ok);
+    let (status, response) = post_form(12017, "/token", None, &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::InvalidClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_authn_method_mismatch() {
+    let (_, mut listening) = start_server(12019, 12020, None);
+    let mut client = test_client("1", 12019);
+
+    // Client "1" is registered for client_secret_basic
+    client.authn_method = ClientAuthnMethod::ClientSecretPost;
+    let result = client.request_client_credentials_token(None, client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_refresh_token_grant() {
+    let (server, mut listening) = start_server(12021, 12022, None);
+    let client_data = test_client_data("1", 12022);
+    let refresh_token = server.lock().unwrap().issue_token(&client_data.client_id, None, true)
+        .refresh_token.unwrap();
+    let body = format!("grant_type=refresh_token&refresh_token={}", refresh_token);
+
+    let (status, _) = post_form(12021, "/token", Some(&client_data), &*body);
+    assert_eq!(status, StatusCode::Ok);
+
+    // The refresh token was rotated, so cannot be used again
+    let (status, _) = post_form(12021, "/token", Some(&client_data), &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_refresh_access_token() {
+    let (server, mut listening) = start_server(12055, 12056, None);
+    let client = test_client("1", 12055);
+    let refresh_token = server.lock().unwrap()
+        .issue_token(&client.get_client_data().client_id, None, true)
+        .refresh_token.unwrap();
+
+    let refreshed = client.refresh_access_token(&*refresh_token, None,
+                                                client.endpoint("/token"))
+        .unwrap().unwrap();
+    assert!(refreshed.refresh_token.is_some());
+
+    // The refresh token was rotated, so cannot be used again
+    let result = client.refresh_access_token(&*refresh_token, None, client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidGrant);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_credentials_grant() {
+    let (_, mut listening) = start_server(12023, 12024, None);
+
+    let client = test_client("1", 12023);
+    let result = client.request_client_credentials_token(
+        Some("read".to_owned()), client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap().scope, Some("read".to_owned()));
+
+    let restricted = test_client("read", 12023);
+    let result = restricted.request_client_credentials_token(
+        Some("write".to_owned()), restricted.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidScope);
+
+    let public = test_client("public", 12023);
+    let result = public.request_client_credentials_token(None, public.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::UnauthorizedClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_password_grant() {
+    let (_, mut listening) = start_server(12025, 12026, None);
+    let client_data = test_client_data("1", 12026);
+
+    let (status, _) = post_form(12025, "/token", Some(&client_data),
+                                "grant_type=password&username=alice&password=wonderland");
+    assert_eq!(status, StatusCode::Ok);
+
+    let (status, body) = post_form(12025, "/token", Some(&client_data),
+                                   "grant_type=password&username=alice&password=guess");
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*body), TokenErrorCode::InvalidGrant);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_device_code_grant() {
+    let (server, mut listening) = start_server(12027, 12028, None);
+    let client = test_client("1", 12027);
+
+    let device_authorization = client.request_device_authorization(
+        None, client.endpoint("/device")).unwrap().unwrap();
+    let body = Serializer::new(String::new())
+        .append_pair("grant_type", DEVICE_CODE_GRANT_TYPE)
+        .append_pair("device_code", &*device_authorization.device_code)
+        .finish();
+
+    let (status, response) = post_form(12027, "/token", Some(client.get_client_data()), &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::AuthorizationPending);
+
+    // The user approves at the verification URI
+    server.lock().unwrap().device_authorizations
+        .get_mut(&device_authorization.device_code).unwrap().1 = true;
+
+    let (status, _) = post_form(12027, "/token", Some(client.get_client_data()), &*body);
+    assert_eq!(status, StatusCode::Ok);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_device_authorization_rejected() {
+    let (_, mut listening) = start_server(12029, 12030, None);
+    let mut client = test_client("1", 12029);
+    client.client_data.credentials = "wrong".to_owned();
+
+    let result = client.request_device_authorization(None, client.endpoint("/device"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidClient);
+
+    // Client "read" may only request the read scope
+    let restricted = test_client("read", 12029);
+    let result = restricted.request_device_authorization(Some("write".to_owned()),
+                                                         restricted.endpoint("/device"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidScope);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_token_exchange_grant() {
+    let (_, mut listening) = start_server(12031, 12032, None);
+    let client = test_client("1", 12031);
+
+    let token = client.request_client_credentials_token(None, client.endpoint("/token"))
+        .unwrap().unwrap();
+    let request = TokenExchangeRequest::new(&*token.access_token, TokenType::AccessToken);
+    let exchanged = client.exchange_token(&request, client.endpoint("/token"))
+        .unwrap().unwrap();
+    assert_eq!(exchanged.issued_token_type,
+               Some("urn:ietf:params:oauth:token-type:access_token".to_owned()));
+
+    let request = TokenExchangeRequest::new("unknown", TokenType::AccessToken);
+    let result = client.exchange_token(&request, client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidRequest);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_jwt_bearer_grant() {
+    let (_, mut listening) = start_server(12033, 12034, None);
+    let client = test_client("1", 12033);
+    let key = HmacKey { key: SERVICE_KEY.to_vec(), key_id: None };
+
+    let assertion = sign_jwt_assertion("service", "alice", "http://127.0.0.1:12033", 60,
+                                       &key).unwrap();
+    let result = client.request_jwt_bearer_token(&*assertion, None, client.endpoint("/token"));
+    assert!(result.unwrap().is_ok());
+
+    let assertion = sign_jwt_assertion("stranger", "alice", "http://127.0.0.1:12033", 60,
+                                       &key).unwrap();
+    let result = client.request_jwt_bearer_token(&*assertion, None, client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidGrant);
+
+    // The scope defaults to everything the client is allowed
+    let assertion = sign_jwt_assertion("service", "alice", "http://127.0.0.1:12033", 60,
+                                       &key).unwrap();
+    let restricted = test_client("read", 12033);
+    let token = restricted.request_jwt_bearer_token(&*assertion, None,
+                                                    restricted.endpoint("/token"))
+        .unwrap().unwrap();
+    assert_eq!(token.scope, Some("read".to_owned()));
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_saml2_bearer_grant() {
+    let (_, mut listening) = start_server(12035, 12036, None);
+    let client = test_client("1", 12035);
+
+    let result = client.request_saml2_bearer_token(SAML2_ASSERTION, None,
+                                                   client.endpoint("/token"));
+    assert!(result.unwrap().is_ok());
+
+    let result = client.request_saml2_bearer_token(b"<saml:Assertion/>", None,
+                                                   client.endpoint("/token"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidGrant);
+
+    // The scope defaults to everything the client is allowed
+    let restricted = test_client("read", 12035);
+    let token = restricted.request_saml2_bearer_token(SAML2_ASSERTION, None,
+                                                      restricted.endpoint("/token"))
+        .unwrap().unwrap();
+    assert_eq!(token.scope, Some("read".to_owned()));
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_ciba_grant() {
+    let (server, mut listening) = start_server(12037, 12038, None);
+    let client = test_client("ciba", 12037);
+
+    let backchannel_response = client.request_backchannel_authentication(
+        &BackchannelAuthRequest::new("", "alice"), client.endpoint("/bc-authorize"))
+        .unwrap().unwrap();
+    let body = Serializer::new(String::new())
+        .append_pair("grant_type", CIBA_GRANT_TYPE)
+        .append_pair("auth_req_id", &*backchannel_response.auth_req_id)
+        .finish();
+
+    let (status, response) = post_form(12037, "/token", Some(client.get_client_data()), &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::AuthorizationPending);
+
+    // The user approves on their device
+    {
+        let mut server = server.lock().unwrap();
+        let client_id = ClientId("ciba".to_owned());
+        let token = server.issue_token(&client_id, Some("openid"), false);
+        server.complete_backchannel_authentication(
+            &mut (), &client_id, &*backchannel_response.auth_req_id, None,
+            BackchannelAuthResult::Granted(token)).unwrap();
+    }
+
+    let (status, _) = post_form(12037, "/token", Some(client.get_client_data()), &*body);
+    assert_eq!(status, StatusCode::Ok);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_ciba_rejected() {
+    let (_, mut listening) = start_server(12039, 12040, None);
+
+    let client = test_client("ciba", 12039);
+    let result = client.request_backchannel_authentication(
+        &BackchannelAuthRequest::new("", "mallory"), client.endpoint("/bc-authorize"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::UnknownUserId);
+
+    // Client "ciba" may only request the openid scope
+    let result = client.request_backchannel_authentication(
+        &BackchannelAuthRequest::new("email", "alice"), client.endpoint("/bc-authorize"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidScope);
+
+    // Only clients registered for backchannel authentication may poll
+    let body = Serializer::new(String::new())
+        .append_pair("grant_type", CIBA_GRANT_TYPE)
+        .append_pair("auth_req_id", "unknown")
+        .finish();
+    let (status, response) = post_form(12039, "/token", Some(&test_client_data("1", 12040)),
+                                       &*body);
+    assert_eq!(status, StatusCode::BadRequest);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::UnauthorizedClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_revocation() {
+    let (server, mut listening) = start_server(12041, 12042, None);
+    let client = test_client("1", 12041);
+
+    let token = client.request_client_credentials_token(None, client.endpoint("/token"))
+        .unwrap().unwrap();
+    let result = client.revoke_token(&*token.access_token, Some(TokenTypeHint::AccessToken),
+                                     client.endpoint("/revoke"));
+    assert!(result.unwrap().is_ok());
+    assert!(! server.lock().unwrap().access_tokens.contains_key(&token.access_token));
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_revocation_rejected() {
+    let (_, mut listening) = start_server(12043, 12044, None);
+    let mut client = test_client("1", 12043);
+    client.client_data.credentials = "wrong".to_owned();
+
+    let result = client.revoke_token("unknown", None, client.endpoint("/revoke"));
+    assert_eq!(result.unwrap().unwrap_err().error, TokenErrorCode::InvalidClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_introspection() {
+    let (_, mut listening) = start_server(12045, 12046, None);
+    let client = test_client("1", 12045);
+
+    let token = client.request_client_credentials_token(None, client.endpoint("/token"))
+        .unwrap().unwrap();
+    let body = Serializer::new(String::new())
+        .append_pair("token", &*token.access_token)
+        .finish();
+    let (status, response) = post_form(12045, "/introspect", Some(client.get_client_data()),
+                                       &*body);
+    assert_eq!(status, StatusCode::Ok);
+    let introspection: TokenIntrospection = serde_json::from_str(&*response).unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.client_id, Some("1".to_owned()));
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_introspection_rejected() {
+    let (_, mut listening) = start_server(12047, 12048, None);
+
+    let mut client_data = test_client_data("1", 12048);
+    client_data.credentials = "wrong".to_owned();
+    let (status, response) = post_form(12047, "/introspect", Some(&client_data),
+                                       "token=unknown");
+    assert_eq!(status, StatusCode::Unauthorized);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::InvalidClient);
+
+    // Public clients may not introspect tokens
+    let (status, response) = post_form(12047, "/introspect", None,
+                                       "token=unknown&client_id=public");
+    assert_eq!(status, StatusCode::Unauthorized);
+    assert_eq!(token_error_code(&*response), TokenErrorCode::InvalidClient);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_registration() {
+    let (_, mut listening) = start_server(12049, 12050, None);
+
+    let user_agent = HttpClient::new();
+    let res = user_agent.post("http://127.0.0.1:12049/register")
+        .header(ContentType::json())
+        .body(r#"{"redirect_uris": ["https://client.example.org/callback"]}"#)
+        .send().unwrap();
+    let (status, body) = read_response(res);
+    assert_eq!(status, StatusCode::Created);
+
+    let registration: serde_json::Value = serde_json::from_str(&*body).unwrap();
+    let client_id = registration.find("client_id").unwrap().as_string().unwrap();
+    let token = registration.find("registration_access_token").unwrap()
+        .as_string().unwrap();
+    let uri = registration.find("registration_client_uri").unwrap().as_string().unwrap();
+
+    let (status, _) = configure(Method::Get, uri, token, None);
+    assert_eq!(status, StatusCode::Ok);
+
+    let update = format!(r#"{{"client_id": "{}",
+                              "redirect_uris": ["https://client.example.org/other"]}}"#,
+                         client_id);
+    let (status, body) = configure(Method::Put, uri, token, Some(&*update));
+    assert_eq!(status, StatusCode::Ok);
+    assert!(body.contains("https://client.example.org/other"));
+
+    let (status, _) = configure(Method::Delete, uri, token, None);
+    assert_eq!(status, StatusCode::NoContent);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_registration_rejected() {
+    let (_, mut listening) = start_server(12051, 12052, None);
+    let user_agent = HttpClient::new();
+
+    // Redirect URIs must not include a fragment
+    let res = user_agent.post("http://127.0.0.1:12051/register")
+        .header(ContentType::json())
+        .body(r#"{"redirect_uris": ["https://client.example.org/callback#top"]}"#)
+        .send().unwrap();
+    let (status, body) = read_response(res);
+    assert_eq!(status, StatusCode::BadRequest);
+    let error: RegistrationError = serde_json::from_str(&*body).unwrap();
+    assert_eq!(error.error, RegistrationErrorCode::InvalidRedirectUri);
+
+    // Redirect URIs must use https, except to the loopback interface
+    let res = user_agent.post("http://127.0.0.1:12051/register")
+        .header(ContentType::json())
+        .body(r#"{"redirect_uris": ["http://client.example.org/callback"]}"#)
+        .send().unwrap();
+    let (status, body) = read_response(res);
+    assert_eq!(status, StatusCode::BadRequest);
+    let error: RegistrationError = serde_json::from_str(&*body).unwrap();
+    assert_eq!(error.error, RegistrationErrorCode::InvalidRedirectUri);
+
+    let _ = listening.close();
+}
+
+#[test]
+fn test_client_configuration_rejected() {
+    let (_, mut listening) = start_server(12053, 12054, None);
+
+    let user_agent = HttpClient::new();
+    let res = user_agent.post("http://127.0.0.1:12053/register")
+        .header(ContentType::json())
+        .body(r#"{"redirect_uris": ["https://client.example.org/callback"]}"#)
+        .send().unwrap();
+    let (status, body) = read_response(res);
+    assert_eq!(status, StatusCode::Created);
+    let registration: serde_json::Value = serde_json::from_str(&*body).unwrap();
+    let uri = registration.find("registration_client_uri").unwrap().as_string().unwrap();
+
+    // The registration access token is required
+    let (status, _) = configure(Method::Get, uri, "wrong", None);
+    assert_eq!(status, StatusCode::Unauthorized);
+
+    let _ = listening.close();
+}